@@ -0,0 +1,69 @@
+// Bits that always read back as 1 for 0xFF10..=0xFF2F. Write-only fields
+// such as the frequency low bytes and length timers read as all 1s.
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // unused, NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // unused, NR41-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // unused
+];
+
+// Sound registers and wave RAM. No audio is generated yet, but the
+// registers behave like hardware so games can read them back.
+pub struct APU {
+    registers: [u8; 0x20],
+    pub wave_ram: [u8; 0x10],
+    powered: bool,
+    // channel status bits of NR52, read-only to the CPU
+    channels_on: u8,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            registers: [0; 0x20],
+            wave_ram: [0; 0x10],
+            powered: false,
+            channels_on: 0,
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF26 => 0x70 | if self.powered { 0x80 } else { 0x00 } | self.channels_on,
+            0xFF10..=0xFF2F => {
+                let index = (address - 0xFF10) as usize;
+                self.registers[index] | READ_MASKS[index]
+            }
+            0xFF30..=0xFF3F => self.wave_ram[(address - 0xFF30) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF26 => {
+                self.powered = byte & 0x80 != 0;
+                // powering off clears every sound register
+                if !self.powered {
+                    self.registers = [0; 0x20];
+                    self.channels_on = 0;
+                }
+            }
+            // the other registers ignore writes while the APU is off
+            0xFF10..=0xFF25 if self.powered => {
+                self.registers[(address - 0xFF10) as usize] = byte;
+            }
+            0xFF30..=0xFF3F => self.wave_ram[(address - 0xFF30) as usize] = byte,
+            _ => {}
+        }
+    }
+
+    // Nothing plays yet, so only restored state marks channels as on
+    pub fn set_channels_on(&mut self, channels: u8) {
+        if self.powered {
+            self.channels_on = channels & 0x0F;
+        }
+    }
+}
@@ -1,56 +1,460 @@
+use crate::apu::APU;
+use crate::cartridge::Cartridge;
+use crate::cpu::T_CYCLES_PER_M_CYCLE;
+use crate::dma::Dma;
+use crate::interrupts::{Interrupt, InterruptController};
+use crate::joypad::{Button, Joypad};
+use crate::model::Model;
 use crate::ppu::PPU;
+use crate::serial::Serial;
+use crate::timer::Timer;
 
 pub struct MemoryBus {
-    memory: [u8; 0x10000],
+    pub model: Model,
+    wram: [u8; 0x2000],
+    hram: [u8; 0x7F],
+    pub cartridge: Option<Cartridge>,
+    boot_rom: Vec<u8>,
     pub ppu: PPU,
+    pub apu: APU,
+    pub timer: Timer,
+    pub joypad: Joypad,
+    pub serial: Serial,
+    pub interrupts: InterruptController,
+    pub dma: Dma,
     pub boot_enabled: bool,
 }
 
 impl MemoryBus {
-    pub fn new() -> Self {
+    pub fn new(model: Model) -> Self {
         MemoryBus {
-            memory: [0; 0x10000],
+            model,
+            wram: [0; 0x2000],
+            hram: [0; 0x7F],
+            cartridge: None,
+            boot_rom: Vec::new(),
             ppu: PPU::new(),
-            boot_enabled: true,
+            apu: APU::new(),
+            timer: Timer::new(),
+            joypad: Joypad::new(),
+            serial: Serial::new(),
+            interrupts: InterruptController::new(),
+            dma: Dma::new(),
+            boot_enabled: false,
         }
     }
 
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        self.cartridge = Some(cartridge);
+    }
+
+    // The boot ROM shadows the start of the cartridge until 0xFF50 is written
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_enabled = !boot_rom.is_empty();
+        self.boot_rom = boot_rom;
+    }
+
+    // I/O state the boot ROM leaves behind, for starting straight at 0x0100
+    pub fn skip_boot(&mut self) {
+        self.boot_enabled = false;
+
+        // the dot within line 153 picks the STAT the game sees: DMG0 hands
+        // over while the LY=LYC compare is still settling (0x81), the
+        // others once LY=0 matches (0x85)
+        let (div, line_dot, sc, dma) = match self.model {
+            Model::DMG0 => (0x1800, 4, 0x7E, 0xFF),
+            Model::DMG | Model::MGB => (0xABCC, 8, 0x7E, 0xFF),
+            Model::SGB => (0x0000, 8, 0x7E, 0xFF),
+            Model::CGB => (0x267C, 8, 0x7F, 0x00),
+        };
+
+        self.timer.set_counter(div);
+        self.serial.write(0xFF02, sc);
+        self.joypad.write(0xCF);
+        self.interrupts.write_flag(0xE1);
+        self.interrupts.write_enable(0x00);
+        self.dma.register = dma;
+
+        // the APU has to be powered before its other registers take writes.
+        // Channel 1 is still on from the boot chime, except on the SGB.
+        self.apu.write(0xFF26, 0x80);
+        if self.model != Model::SGB {
+            self.apu.set_channels_on(0x01);
+        }
+        for (address, byte) in [
+            (0xFF10, 0x80),
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF13, 0xFF),
+            (0xFF14, 0xBF),
+            (0xFF16, 0x3F),
+            (0xFF17, 0x00),
+            (0xFF18, 0xFF),
+            (0xFF19, 0xBF),
+            (0xFF1A, 0x7F),
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1D, 0xFF),
+            (0xFF1E, 0xBF),
+            (0xFF20, 0xFF),
+            (0xFF21, 0x00),
+            (0xFF22, 0x00),
+            (0xFF23, 0xBF),
+            (0xFF24, 0x77),
+            (0xFF25, 0xF3),
+        ] {
+            self.apu.write(address, byte);
+        }
+
+        for (address, byte) in [
+            (0xFF40, 0x91),
+            (0xFF41, 0x00),
+            (0xFF42, 0x00),
+            (0xFF43, 0x00),
+            (0xFF45, 0x00),
+            (0xFF47, 0xFC),
+            (0xFF48, 0xFF),
+            (0xFF49, 0xFF),
+            (0xFF4A, 0x00),
+            (0xFF4B, 0x00),
+        ] {
+            self.ppu.write_register(address, byte);
+        }
+        self.ppu.skip_boot(line_dot);
+    }
+
+    // Advances everything clocked alongside the CPU by `cycles` T-cycles
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles / T_CYCLES_PER_M_CYCLE {
+            if let Some((source, index)) = self.dma.step() {
+                let byte = self.read_dma_source(source);
+                self.ppu.oam[index] = byte;
+                self.dma.bus_value = byte;
+            }
+        }
+
+        if self.timer.tick(cycles) {
+            self.interrupts.request(Interrupt::Timer);
+        }
+
+        if self.serial.tick(cycles) {
+            self.interrupts.request(Interrupt::Serial);
+        }
+
+        if self.ppu.tick(cycles) {
+            self.interrupts.request(Interrupt::VBlank);
+        }
+        if self.ppu.take_stat_interrupt() {
+            self.interrupts.request(Interrupt::LcdStat);
+        }
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if self.joypad.set_button(button, pressed) {
+            self.interrupts.request(Interrupt::Joypad);
+        }
+    }
+
+    // CPU reads. While OAM DMA runs, OAM is locked and anything on the
+    // same bus as the DMA source returns whatever the DMA is reading.
     pub fn read_byte(&self, address: u16) -> u8 {
+        if let Some(source) = self.dma.source() {
+            match address {
+                0xFE00..=0xFEFF => return 0xFF,
+                _ if Self::conflicts(source, address) => return self.dma.bus_value,
+                _ => {}
+            }
+        }
+        if self.ppu_locked(address) {
+            return 0xFF;
+        }
+        self.read_mapped(address)
+    }
+
+    // CPU writes to OAM or the DMA's bus are lost during a transfer
+    pub fn write_byte(&mut self, address: u16, byte: u8) {
+        if let Some(source) = self.dma.source() {
+            match address {
+                0xFE00..=0xFEFF => return,
+                _ if Self::conflicts(source, address) => return,
+                _ => {}
+            }
+        }
+        if self.ppu_locked(address) {
+            return;
+        }
+        self.write_mapped(address, byte);
+    }
+
+    // VRAM and OAM while the PPU is using them, reads return 0xFF and
+    // writes are dropped. The unusable region after OAM is blocked with it.
+    fn ppu_locked(&self, address: u16) -> bool {
         match address {
+            0x8000..=0x9FFF => !self.ppu.vram_accessible(),
+            0xFE00..=0xFEFF => !self.ppu.oam_accessible(),
+            _ => false,
+        }
+    }
+
+    // VRAM sits on its own bus, everything below 0xFE00 shares the
+    // external one. I/O, HRAM and IE are internal and never conflict.
+    fn conflicts(source: u16, address: u16) -> bool {
+        let video = |address: u16| (0x8000..=0x9FFF).contains(&address);
+        address < 0xFE00 && video(source) == video(address)
+    }
+
+    // Sources above 0xDFFF read WRAM, like echo RAM does
+    fn read_dma_source(&self, source: u16) -> u8 {
+        match source {
+            0xE000..=0xFFFF => self.wram[((source - 0xE000) & 0x1FFF) as usize],
+            _ => self.read_mapped(source),
+        }
+    }
+
+    fn read_mapped(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x00FF if self.boot_enabled && (address as usize) < self.boot_rom.len() => {
+                self.boot_rom[address as usize]
+            }
+            // with no cartridge inserted the data lines float high
+            0x0000..=0x7FFF => match &self.cartridge {
+                Some(cartridge) => cartridge.read_rom(address),
+                None => 0xFF,
+            },
             0x8000..=0x9FFF => self.ppu.vram[(address - 0x8000) as usize],
-            0xFF44 => self.ppu.ly,
-            0xFF00..=0xFF7F => self.memory[address as usize],
-            _ => self.memory[address as usize],
+            0xA000..=0xBFFF => match &self.cartridge {
+                Some(cartridge) => cartridge.read_ram(address),
+                None => 0xFF,
+            },
+            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize],
+            // echo RAM, the chip select ignores address bit 13
+            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize],
+            0xFE00..=0xFE9F => self.ppu.oam[(address - 0xFE00) as usize],
+            0xFEA0..=0xFEFF => self.read_unusable(address),
+            0xFF00..=0xFF7F => self.read_io(address),
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize],
+            0xFFFF => self.interrupts.read_enable(),
         }
     }
 
-    pub fn write_byte(&mut self, address: u16, byte: u8) {
+    fn write_mapped(&mut self, address: u16, byte: u8) {
         match address {
+            // writes to the ROM area program the cartridge's mapper
+            0x0000..=0x7FFF => {
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.write_rom(address, byte);
+                }
+            }
             0x8000..=0x9FFF => self.ppu.vram[(address - 0x8000) as usize] = byte,
-            0xFF44 => self.ppu.ly = 0,
-            0xFF50 => {
-                if byte != 0 && self.boot_enabled {
-                    println!("BOOTROM disabled, switching to GameROM");
-                    self.boot_enabled = false;
+            0xA000..=0xBFFF => {
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.write_ram(address, byte);
                 }
             }
-            _ => self.memory[address as usize] = byte,
+            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize] = byte,
+            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize] = byte,
+            0xFE00..=0xFE9F => self.ppu.oam[(address - 0xFE00) as usize] = byte,
+            // nothing is connected to 0xFEA0..=0xFEFF
+            0xFEA0..=0xFEFF => {}
+            0xFF00..=0xFF7F => self.write_io(address, byte),
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize] = byte,
+            0xFFFF => self.interrupts.write_enable(byte),
         }
     }
 
-    // little endian, read low byte then high
-    pub fn read_word(&self, address: u16) -> u16 {
-        let low = self.read_byte(address) as u16;
-        let high = self.read_byte(address + 1) as u16;
+    // The unusable region after OAM reads as 0 on monochrome models, while
+    // the CGB repeats the high nibble of the low address byte
+    fn read_unusable(&self, address: u16) -> u8 {
+        match self.model {
+            Model::CGB => {
+                let nibble = (address as u8) & 0xF0;
+                nibble | nibble >> 4
+            }
+            _ => 0x00,
+        }
+    }
 
-        (high << 8) | low
+    // Routes each I/O register to the component that owns it. Unused bits
+    // are filled in by the owners, unmapped registers read as 0xFF.
+    fn read_io(&self, address: u16) -> u8 {
+        match address {
+            0xFF00 => self.joypad.read(),
+            0xFF01..=0xFF02 => self.serial.read(address),
+            0xFF04..=0xFF07 => self.timer.read(address),
+            0xFF0F => self.interrupts.read_flag(),
+            0xFF10..=0xFF3F => self.apu.read(address),
+            0xFF46 => self.dma.register,
+            0xFF40..=0xFF4B => self.ppu.read_register(address),
+            _ => 0xFF,
+        }
     }
 
-    pub fn write_word(&mut self, address: u16, value: u16) {
-        let low = (value & 0x00FF) as u8;
-        let high = ((value & 0xFF00) >> 8) as u8;
+    fn write_io(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF00 => self.joypad.write(byte),
+            0xFF01..=0xFF02 => self.serial.write(address, byte),
+            0xFF04..=0xFF07 => self.timer.write(address, byte),
+            0xFF0F => self.interrupts.write_flag(byte),
+            0xFF10..=0xFF3F => self.apu.write(address, byte),
+            0xFF46 => self.dma.start(byte),
+            0xFF40..=0xFF4B => self.ppu.write_register(address, byte),
+            0xFF50 if byte != 0 => self.boot_enabled = false,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_ram_and_unusable_region() {
+        let mut bus = MemoryBus::new(Model::DMG);
+        bus.write_byte(0xC123, 0x42);
+        assert_eq!(bus.read_byte(0xE123), 0x42);
+        bus.write_byte(0xFDFF, 0x24);
+        assert_eq!(bus.read_byte(0xDDFF), 0x24);
+
+        bus.write_byte(0xFEA5, 0x99);
+        assert_eq!(bus.read_byte(0xFEA5), 0x00);
+        bus.model = Model::CGB;
+        assert_eq!(bus.read_byte(0xFEB5), 0xBB);
+
+        // ROM writes only reach the mapper, never the image
+        bus.load_cartridge(Cartridge::new(vec![0; 0x8000]).unwrap());
+        bus.write_byte(0x0100, 0x12);
+        assert_eq!(bus.read_byte(0x0100), 0x00);
+    }
+
+    #[test]
+    fn io_unused_bits_and_unmapped_registers() {
+        let mut bus = MemoryBus::new(Model::DMG);
+        for (address, byte, expected) in [
+            (0xFF00, 0x00, 0xCF),
+            (0xFF02, 0x00, 0x7E),
+            (0xFF07, 0x00, 0xF8),
+            (0xFF0F, 0x00, 0xE0),
+            // LY=0 with the LCD off, keep LYC from matching it
+            (0xFF45, 0x01, 0x01),
+            (0xFF41, 0x00, 0x80),
+        ] {
+            bus.write_byte(address, byte);
+            assert_eq!(bus.read_byte(address), expected, "{:04X}", address);
+        }
+
+        for address in [0xFF03, 0xFF08, 0xFF0E, 0xFF4C, 0xFF50, 0xFF7F] {
+            bus.write_byte(address, 0x00);
+            assert_eq!(bus.read_byte(address), 0xFF, "{:04X}", address);
+        }
+    }
+
+    #[test]
+    fn timer_serial_and_joypad_raise_interrupts() {
+        let mut bus = MemoryBus::new(Model::DMG);
+        bus.write_byte(0xFF0F, 0x00);
+
+        bus.write_byte(0xFF05, 0xFF);
+        bus.write_byte(0xFF07, 0x05);
+        bus.tick(16);
+        assert_eq!(bus.read_byte(0xFF0F) & 0x04, 0x00);
+        bus.tick(4);
+        assert_eq!(bus.read_byte(0xFF0F) & 0x04, 0x04);
+
+        bus.write_byte(0xFF02, 0x81);
+        bus.tick(8 * 512);
+        assert_eq!(bus.read_byte(0xFF0F) & 0x08, 0x08);
+
+        bus.write_byte(0xFF00, 0x10);
+        bus.set_button(Button::B, true);
+        assert_eq!(bus.read_byte(0xFF0F) & 0x10, 0x10);
+    }
+
+    #[test]
+    fn boot_rom_shadows_the_cartridge_until_disabled() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0000] = 0xC3;
+        rom[0x00FF] = 0xC4;
+        rom[0x0100] = 0xC5;
+        let mut bus = MemoryBus::new(Model::DMG);
+        bus.load_cartridge(Cartridge::new(rom).unwrap());
+        bus.load_boot_rom(vec![0xB0; 0x100]);
+
+        assert_eq!(bus.read_byte(0x0000), 0xB0);
+        assert_eq!(bus.read_byte(0x00FF), 0xB0);
+        assert_eq!(bus.read_byte(0x0100), 0xC5);
+
+        // writing zero leaves it mapped
+        bus.write_byte(0xFF50, 0x00);
+        assert_eq!(bus.read_byte(0x0000), 0xB0);
+
+        bus.write_byte(0xFF50, 0x01);
+        assert_eq!(bus.read_byte(0x0000), 0xC3);
+        assert_eq!(bus.read_byte(0x00FF), 0xC4);
+        assert_eq!(bus.read_byte(0x0100), 0xC5);
+
+        // and it can't be mapped back in
+        bus.write_byte(0xFF50, 0x00);
+        assert_eq!(bus.read_byte(0x0000), 0xC3);
+    }
+
+    #[test]
+    fn oam_dma_timing_and_conflicts() {
+        let mut bus = MemoryBus::new(Model::DMG);
+        for i in 0..0xA0 {
+            bus.write_byte(0xC100 + i, i as u8);
+        }
+        bus.write_byte(0xFF80, 0x77);
+
+        bus.write_byte(0xFF46, 0xC1);
+        // one M-cycle of setup before OAM locks
+        bus.tick(4);
+        assert_eq!(bus.read_byte(0xFE00), 0x00);
+        bus.tick(4);
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        assert_eq!(bus.read_byte(0xC005), 0x00);
+        assert_eq!(bus.read_byte(0xFF80), 0x77);
+
+        // WRAM reads see the byte the DMA is moving, VRAM is unaffected
+        bus.tick(4 * 10);
+        assert_eq!(bus.read_byte(0xC005), 10);
+        assert_eq!(bus.read_byte(0x8000), 0x00);
+
+        bus.tick(4 * 148);
+        assert_eq!(bus.read_byte(0xFE9F), 0xFF);
+        bus.tick(4);
+        assert_eq!(bus.read_byte(0xFE9F), 0x9F);
+        assert_eq!(bus.read_byte(0xFF46), 0xC1);
+    }
+
+    #[test]
+    fn vram_and_oam_locked_while_the_ppu_uses_them() {
+        let mut bus = MemoryBus::new(Model::DMG);
+        bus.write_byte(0x8000, 0x11);
+        bus.write_byte(0xFE00, 0x22);
+        bus.write_byte(0xFF40, 0x91);
+
+        // OAM scan
+        assert_eq!(bus.read_byte(0x8000), 0x11);
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        assert_eq!(bus.read_byte(0xFEA0), 0xFF);
+
+        // drawing
+        bus.tick(80);
+        bus.write_byte(0x8000, 0x33);
+        assert_eq!(bus.read_byte(0x8000), 0xFF);
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        assert_eq!(bus.read_byte(0xFEFF), 0xFF);
+
+        // HBlank
+        bus.tick(172);
+        assert_eq!(bus.read_byte(0x8000), 0x11);
+        assert_eq!(bus.read_byte(0xFE00), 0x22);
+        assert_eq!(bus.read_byte(0xFEA0), 0x00);
 
-        self.write_byte(address, low);
-        self.write_byte(address + 1, high);
+        // LY ignores writes
+        bus.write_byte(0xFF44, 0x00);
+        bus.tick(456);
+        assert_eq!(bus.read_byte(0xFF44), 1);
     }
 }
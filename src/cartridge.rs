@@ -0,0 +1,434 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::mbc::{
+    HuC1, HuC3, MBC1, MBC2, MBC3, MBC5, MBC6, MBC7, MMM01, Mapper, PocketCamera, RomOnly,
+    RumbleCallback, TAMA5,
+};
+
+const HEADER_END: usize = 0x0150;
+const MMM01_MENU_SIZE: usize = 0x8000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CgbSupport {
+    None,
+    Enhanced,
+    Only,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError {
+    Truncated { len: usize },
+    InvalidRomSize(u8),
+    InvalidRamSize(u8),
+    RomSizeMismatch { expected: usize, actual: usize },
+    HeaderChecksum { expected: u8, actual: u8 },
+    GlobalChecksum { expected: u16, actual: u16 },
+    UnsupportedMapper(u8),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Truncated { len } => {
+                write!(f, "ROM is {} bytes, too short to hold a header", len)
+            }
+            CartridgeError::InvalidRomSize(code) => {
+                write!(f, "invalid ROM size code {:#04x}", code)
+            }
+            CartridgeError::InvalidRamSize(code) => {
+                write!(f, "invalid RAM size code {:#04x}", code)
+            }
+            CartridgeError::RomSizeMismatch { expected, actual } => write!(
+                f,
+                "header declares {} bytes of ROM but the image is {} bytes",
+                expected, actual
+            ),
+            CartridgeError::HeaderChecksum { expected, actual } => write!(
+                f,
+                "header checksum is {:#04x}, computed {:#04x}",
+                expected, actual
+            ),
+            CartridgeError::GlobalChecksum { expected, actual } => write!(
+                f,
+                "global checksum is {:#06x}, computed {:#06x}",
+                expected, actual
+            ),
+            CartridgeError::UnsupportedMapper(cartridge_type) => {
+                write!(f, "unsupported cartridge type {:#04x}", cartridge_type)
+            }
+        }
+    }
+}
+
+impl Error for CartridgeError {}
+
+// Metadata from 0x0100..=0x014F
+#[derive(Clone, Debug, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb_support: CgbSupport,
+    pub sgb_support: bool,
+    pub cartridge_type: u8,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    pub destination: Destination,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, CartridgeError> {
+        if rom.len() < HEADER_END {
+            return Err(CartridgeError::Truncated { len: rom.len() });
+        }
+
+        let cgb_support = match rom[0x0143] {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        };
+
+        // CGB-era carts shortened the title to make room for the
+        // manufacturer code and the CGB flag
+        let (title_end, manufacturer_code) = match cgb_support {
+            CgbSupport::None => (0x0144, None),
+            _ => {
+                let code = &rom[0x013F..0x0143];
+                let valid = code
+                    .iter()
+                    .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+                if valid {
+                    (0x013F, Some(String::from_utf8_lossy(code).into_owned()))
+                } else {
+                    (0x0143, None)
+                }
+            }
+        };
+
+        let title = rom[0x0134..title_end]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| b as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+
+        let rom_size = match rom[0x0148] {
+            code @ 0x00..=0x08 => 0x8000 << code,
+            code => return Err(CartridgeError::InvalidRomSize(code)),
+        };
+
+        let ram_size = match rom[0x0149] {
+            0x00 => 0,
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            code => return Err(CartridgeError::InvalidRamSize(code)),
+        };
+
+        Ok(CartridgeHeader {
+            title,
+            manufacturer_code,
+            cgb_support,
+            sgb_support: rom[0x0146] == 0x03,
+            cartridge_type: rom[0x0147],
+            rom_size,
+            ram_size,
+            destination: if rom[0x014A] == 0x00 {
+                Destination::Japan
+            } else {
+                Destination::Overseas
+            },
+            version: rom[0x014C],
+            header_checksum: rom[0x014D],
+            global_checksum: (rom[0x014E] as u16) << 8 | rom[0x014F] as u16,
+        })
+    }
+
+    // Cartridge types whose RAM survives power-off, either thanks to a
+    // battery or, for MBC6, because it sits next to flash
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.cartridge_type,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x20 | 0x22 | 0xFC
+                ..=0xFF
+        )
+    }
+
+    pub fn has_rtc(&self) -> bool {
+        matches!(self.cartridge_type, 0x0F | 0x10)
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        matches!(self.cartridge_type, 0x1C..=0x1E)
+    }
+}
+
+pub struct Cartridge {
+    pub header: CartridgeHeader,
+    // where the header was found, only non-zero for MMM01 multicarts
+    header_base: usize,
+    mapper: Box<dyn Mapper>,
+    // set by writes to 0xA000..=0xBFFF, cleared by the save file
+    ram_written: bool,
+}
+
+impl Cartridge {
+    pub fn new(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        // MMM01 dumps start with the first game; the header describing the
+        // whole cart belongs to the menu in the last 32 KiB
+        let menu_base = rom.len().saturating_sub(MMM01_MENU_SIZE);
+        let (header, header_base) = match CartridgeHeader::parse(&rom[menu_base..]) {
+            Ok(header) if menu_base > 0 && matches!(header.cartridge_type, 0x0B..=0x0D) => {
+                (header, menu_base)
+            }
+            _ => (CartridgeHeader::parse(&rom)?, 0),
+        };
+
+        if rom.len() != header.rom_size {
+            return Err(CartridgeError::RomSizeMismatch {
+                expected: header.rom_size,
+                actual: rom.len(),
+            });
+        }
+
+        let ram_size = header.ram_size;
+        let mapper: Box<dyn Mapper> = match header.cartridge_type {
+            0x00 | 0x08 | 0x09 => Box::new(RomOnly::new(rom, ram_size)),
+            0x01..=0x03 => Box::new(MBC1::new(rom, ram_size)),
+            0x05 | 0x06 => Box::new(MBC2::new(rom)),
+            0x0B..=0x0D => Box::new(MMM01::new(rom, ram_size)),
+            0x0F..=0x13 => Box::new(MBC3::new(rom, ram_size, header.has_rtc())),
+            0x19..=0x1E => Box::new(MBC5::new(rom, ram_size, header.has_rumble())),
+            0x20 => Box::new(MBC6::new(rom, ram_size)),
+            0x22 => Box::new(MBC7::new(rom)),
+            0xFC => Box::new(PocketCamera::new(rom, ram_size)),
+            0xFD => Box::new(TAMA5::new(rom)),
+            0xFE => Box::new(HuC3::new(rom, ram_size)),
+            0xFF => Box::new(HuC1::new(rom, ram_size)),
+            cartridge_type => return Err(CartridgeError::UnsupportedMapper(cartridge_type)),
+        };
+
+        Ok(Cartridge {
+            header,
+            header_base,
+            mapper,
+            ram_written: false,
+        })
+    }
+
+    // The boot ROM refuses to start a cartridge whose header checksum is wrong
+    pub fn verify_header_checksum(&self) -> Result<(), CartridgeError> {
+        let header = &self.mapper.rom()[self.header_base..];
+        let actual = header[0x0134..=0x014C]
+            .iter()
+            .fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1));
+
+        if actual != self.header.header_checksum {
+            return Err(CartridgeError::HeaderChecksum {
+                expected: self.header.header_checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    // Sum of every ROM byte except the checksum itself. Hardware never
+    // checks it, so a mismatch only hints at a bad dump or a patched ROM.
+    pub fn verify_global_checksum(&self) -> Result<(), CartridgeError> {
+        let actual = self
+            .mapper
+            .rom()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 0x014E && *i != 0x014F)
+            .fold(0u16, |sum, (_, &b)| sum.wrapping_add(b as u16));
+
+        if actual != self.header.global_checksum {
+            return Err(CartridgeError::GlobalChecksum {
+                expected: self.header.global_checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    // Battery-backed RAM followed by any clock state, in the raw layout
+    // other emulators use
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.mapper.ram().to_vec();
+        data.extend(self.mapper.clock_data());
+        data
+    }
+
+    // Restores a save file, tolerating files that are shorter than the
+    // cartridge RAM or lack the clock trailer
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let ram = self.mapper.ram_mut();
+        let len = ram.len().min(data.len());
+        ram[..len].copy_from_slice(&data[..len]);
+        self.mapper.load_clock_data(&data[len..]);
+    }
+
+    // Lets the frontend follow the rumble motor. Carts without one never
+    // call it.
+    pub fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+        self.mapper.set_rumble_callback(callback);
+    }
+
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mapper.set_tilt(x, y);
+    }
+
+    pub fn read_rom(&self, address: u16) -> u8 {
+        self.mapper.read_rom(address)
+    }
+
+    pub fn write_rom(&mut self, address: u16, byte: u8) {
+        // MBC6 flash is programmed through the ROM area
+        if self.mapper.write_rom(address, byte) {
+            self.ram_written = true;
+        }
+    }
+
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mapper.read_ram(address)
+    }
+
+    pub fn write_ram(&mut self, address: u16, byte: u8) {
+        if self.mapper.write_ram(address, byte) {
+            self.ram_written = true;
+        }
+    }
+
+    // Whether the game wrote to cartridge RAM since the last call
+    pub fn take_ram_written(&mut self) -> bool {
+        std::mem::take(&mut self.ram_written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0134..0x0139].copy_from_slice(b"TETRA");
+        rom[0x014D] = rom[0x0134..=0x014C]
+            .iter()
+            .fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1));
+        rom
+    }
+
+    #[test]
+    fn parses_header_fields() {
+        let mut rom = blank_rom();
+        rom[0x0143] = 0x80;
+        rom[0x013F..0x0143].copy_from_slice(b"ABCD");
+        rom[0x0146] = 0x03;
+        rom[0x0147] = 0x13;
+        rom[0x0148] = 0x00;
+        rom[0x0149] = 0x03;
+        rom[0x014A] = 0x01;
+        rom[0x014C] = 0x02;
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "TETRA");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("ABCD"));
+        assert_eq!(header.cgb_support, CgbSupport::Enhanced);
+        assert!(header.sgb_support);
+        assert_eq!(header.cartridge_type, 0x13);
+        assert_eq!(header.rom_size, 0x8000);
+        assert_eq!(header.ram_size, 0x8000);
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.version, 0x02);
+    }
+
+    #[test]
+    fn rejects_truncated_and_inconsistent_images() {
+        assert_eq!(
+            Cartridge::new(vec![0; 0x100]).err(),
+            Some(CartridgeError::Truncated { len: 0x100 })
+        );
+
+        let mut rom = blank_rom();
+        rom[0x0148] = 0x01;
+        assert_eq!(
+            Cartridge::new(rom).err(),
+            Some(CartridgeError::RomSizeMismatch {
+                expected: 0x10000,
+                actual: 0x8000
+            })
+        );
+
+        let mut rom = blank_rom();
+        rom[0x0147] = 0x04;
+        assert_eq!(
+            Cartridge::new(rom).err(),
+            Some(CartridgeError::UnsupportedMapper(0x04))
+        );
+
+        let mut rom = blank_rom();
+        rom[0x0149] = 0x07;
+        assert_eq!(
+            Cartridge::new(rom).err(),
+            Some(CartridgeError::InvalidRamSize(0x07))
+        );
+    }
+
+    #[test]
+    fn verifies_checksums() {
+        let mut rom = blank_rom();
+        let sum = rom.iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        rom[0x014E] = (sum >> 8) as u8;
+        rom[0x014F] = sum as u8;
+
+        let cartridge = Cartridge::new(rom.clone()).unwrap();
+        assert_eq!(cartridge.verify_header_checksum(), Ok(()));
+        assert_eq!(cartridge.verify_global_checksum(), Ok(()));
+
+        rom[0x0134] = b'X';
+        let cartridge = Cartridge::new(rom).unwrap();
+        assert!(matches!(
+            cartridge.verify_header_checksum(),
+            Err(CartridgeError::HeaderChecksum { .. })
+        ));
+        assert!(matches!(
+            cartridge.verify_global_checksum(),
+            Err(CartridgeError::GlobalChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn only_real_ram_changes_count_as_written() {
+        let mut rom = blank_rom();
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        let mut cartridge = Cartridge::new(rom).unwrap();
+
+        // RAM starts disabled, so the write is dropped
+        cartridge.write_ram(0xA000, 0x42);
+        assert!(!cartridge.take_ram_written());
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        assert!(cartridge.take_ram_written());
+        assert!(!cartridge.take_ram_written());
+
+        // rewriting the same value leaves nothing new to save
+        cartridge.write_ram(0xA000, 0x42);
+        assert!(!cartridge.take_ram_written());
+    }
+}
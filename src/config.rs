@@ -0,0 +1,79 @@
+use crate::palette::Palette;
+use std::fs;
+
+// Read from the working directory when --config isn't given
+pub const DEFAULT_PATH: &str = "dmg01.cfg";
+
+// Settings from a config file of `key = value` lines. Lines starting
+// with '#' are comments.
+//
+//     # classic green, or four colors from lightest to darkest
+//     palette = green
+//     palette = E0F8D0 88C070 346856 081820
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub palette: Option<Palette>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Config, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Config::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (number, line) in text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+        {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected key = value", number))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "palette" => {
+                    config.palette = Some(
+                        Palette::parse(value)
+                            .ok_or(format!("line {}: unknown palette '{}'", number, value))?,
+                    );
+                }
+                key => return Err(format!("line {}: unknown setting '{}'", number, key)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings_and_reports_bad_lines() {
+        let config = Config::parse("# colors\n\n palette = pocket \n").unwrap();
+        assert_eq!(config.palette, Some(Palette::Pocket));
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        assert_eq!(
+            Config::parse("palette = green\nscale 2"),
+            Err(String::from("line 2: expected key = value"))
+        );
+        assert_eq!(
+            Config::parse("palette = 123456"),
+            Err(String::from("line 1: unknown palette '123456'"))
+        );
+        assert_eq!(
+            Config::parse("scale = 2"),
+            Err(String::from("line 1: unknown setting 'scale'"))
+        );
+    }
+}
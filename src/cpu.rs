@@ -1,14 +1,26 @@
-//  TODO: Implement remaining instructions and start
-//        with Program Counter
-
 use crate::bus::MemoryBus;
 use crate::instruction::{ArithmeticTarget, Instruction, JumpTest, Load16Target, StackTarget};
+use crate::interrupts::Interrupt;
+use crate::model::Model;
+
+pub const T_CYCLES_PER_M_CYCLE: u32 = 4;
+
+// With the `mcycle` feature the CPU advances the bus on every M-cycle
+// instead of once per instruction. Slower, but needed for mid-instruction
+// timing such as mooneye's mem_timing tests.
+const M_CYCLE_STEPPED: bool = cfg!(feature = "mcycle");
 
 pub struct CPU {
     pub registers: Registers,
     pub pc: u16,
     pub sp: u16,
     pub ime: bool,
+    ime_pending: bool,
+    pub locked: bool,
+    pub halted: bool,
+    pub stopped: bool,
+    halt_bug: bool,
+    cycles: u32,
 }
 
 impl CPU {
@@ -26,142 +38,117 @@ impl CPU {
             },
             pc: 0,
             sp: 0,
-            ime: true,
+            ime: false,
+            ime_pending: false,
+            locked: false,
+            halted: false,
+            stopped: false,
+            halt_bug: false,
+            cycles: 0,
         }
     }
 
+    // Register state the boot ROM leaves behind when it jumps to 0x0100.
+    // On DMG and MGB the H and C flags depend on the cartridge header checksum.
+    pub fn skip_boot(&mut self, model: Model, header_checksum: u8) {
+        let checksum_flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+
+        let (af, bc, de, hl) = match model {
+            Model::DMG0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+            Model::DMG => (0x0100 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+            Model::MGB => (0xFF00 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+            Model::SGB => (0x0100, 0x0014, 0x0000, 0xC060),
+            Model::CGB => (0x1180, 0x0000, 0xFF56, 0x000D),
+        };
+
+        self.registers.set_af(af);
+        self.registers.set_bc(bc);
+        self.registers.set_de(de);
+        self.registers.set_hl(hl);
+        self.sp = 0xFFFE;
+        self.pc = 0x0100;
+    }
+
     fn execute(&mut self, instruction: Instruction, bus: &mut MemoryBus) {
         match instruction {
             Instruction::ADD(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for ADD!"),
-                };
+                let value = self.read_reg(bus, &target);
                 let result = self.add(value);
                 self.registers.a = result;
             }
 
             Instruction::ADC(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for SUB!"),
-                };
+                let value = self.read_reg(bus, &target);
                 let result = self.adc(value);
                 self.registers.a = result;
             }
 
             Instruction::SUB(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for SUB!"),
-                };
+                let value = self.read_reg(bus, &target);
                 let result = self.sub(value);
                 self.registers.a = result;
             }
 
             Instruction::SBC(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for SUB!"),
-                };
+                let value = self.read_reg(bus, &target);
                 let result = self.sbc(value);
                 self.registers.a = result;
             }
 
+            // CP is a SUB that only keeps the flags
             Instruction::CP(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for CP!"),
-                };
+                let value = self.read_reg(bus, &target);
+                self.sub(value);
+            }
 
-                let (new_value, overflow) = self.registers.a.overflowing_sub(value);
-                self.registers.f.zero = new_value == 0;
-                self.registers.f.subtract = true;
-                self.registers.f.carry = overflow;
-                self.registers.f.half_carry = (self.registers.a & 0xF) < (value & 0xF);
+            Instruction::RLC(target) => {
+                let value = self.read_reg(bus, &target);
+                let new_value = value.rotate_left(1);
+                self.set_shift_flags(new_value, value & 0x80 != 0);
+                self.write_reg(bus, target, new_value);
             }
 
-            Instruction::RL(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    _ => panic!("Unknown target for Instruction RL!"),
-                };
+            Instruction::RRC(target) => {
+                let value = self.read_reg(bus, &target);
+                let new_value = value.rotate_right(1);
+                self.set_shift_flags(new_value, value & 0x01 != 0);
+                self.write_reg(bus, target, new_value);
+            }
 
-                let top_bit = value & 0x80;
+            Instruction::RL(target) => {
+                let value = self.read_reg(bus, &target);
                 let new_value = (value << 1) | (self.registers.f.carry as u8);
-                self.registers.f.carry = top_bit != 0;
+                self.set_shift_flags(new_value, value & 0x80 != 0);
+                self.write_reg(bus, target, new_value);
+            }
 
-                self.registers.f.zero = new_value == 0;
-                self.registers.f.subtract = false;
-                self.registers.f.half_carry = false;
-                self.write_reg(target, new_value);
+            Instruction::RR(target) => {
+                let value = self.read_reg(bus, &target);
+                let new_value = (value >> 1) | ((self.registers.f.carry as u8) << 7);
+                self.set_shift_flags(new_value, value & 0x01 != 0);
+                self.write_reg(bus, target, new_value);
+            }
+
+            Instruction::SLA(target) => {
+                let value = self.read_reg(bus, &target);
+                let new_value = value << 1;
+                self.set_shift_flags(new_value, value & 0x80 != 0);
+                self.write_reg(bus, target, new_value);
+            }
+
+            // arithmetic shift keeps the sign bit in place
+            Instruction::SRA(target) => {
+                let value = self.read_reg(bus, &target);
+                let new_value = (value >> 1) | (value & 0x80);
+                self.set_shift_flags(new_value, value & 0x01 != 0);
+                self.write_reg(bus, target, new_value);
+            }
+
+            Instruction::SRL(target) => {
+                let value = self.read_reg(bus, &target);
+                let new_value = value >> 1;
+                self.set_shift_flags(new_value, value & 0x01 != 0);
+                self.write_reg(bus, target, new_value);
             }
 
             Instruction::RLCA => {
@@ -190,172 +177,116 @@ impl CPU {
                 self.registers.f.zero = false;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
-                self.write_reg(ArithmeticTarget::A, new_value);
+                self.write_reg(bus, ArithmeticTarget::A, new_value);
             }
 
-            Instruction::CPL => self.registers.a = !self.registers.a,
+            // Rotate A right; bit 0 goes to both bit 7 and the carry flag
+            Instruction::RRCA => {
+                let a = self.registers.a;
+                let low_bit = a & 1;
+                self.registers.a = (a >> 1) | (low_bit << 7);
 
-            Instruction::JP(test) => {
-                let jump_condition = match test {
-                    JumpTest::NotZero => !self.registers.f.zero,
-                    JumpTest::Zero => self.registers.f.zero,
-                    JumpTest::NotCarry => !self.registers.f.carry,
-                    JumpTest::Carry => self.registers.f.carry,
-                    JumpTest::Always => true,
-                };
+                self.registers.f.carry = low_bit == 1;
+                self.registers.f.zero = false;
+                self.registers.f.subtract = false;
+                self.registers.f.half_carry = false;
+            }
 
-                let jump_addr = bus.read_word(self.pc);
-                self.pc = self.pc.wrapping_add(2);
+            // Rotate A right through the carry flag
+            Instruction::RRA => {
+                let a = self.registers.a;
+                let low_bit = a & 1;
+                self.registers.a = (a >> 1) | ((self.registers.f.carry as u8) << 7);
 
-                if jump_condition {
-                    self.pc = jump_addr;
-                }
+                self.registers.f.carry = low_bit == 1;
+                self.registers.f.zero = false;
+                self.registers.f.subtract = false;
+                self.registers.f.half_carry = false;
             }
 
-            Instruction::XOR(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for XOR!"),
-                };
+            // Adjust A back into packed BCD after an ADD/ADC or SUB/SBC,
+            // using N, H and C left behind by that operation
+            Instruction::DAA => {
+                let mut a = self.registers.a;
+                let mut correction = 0;
+                let mut carry = self.registers.f.carry;
 
-                let result = self.registers.a ^ value;
-                self.registers.a = result;
+                if self.registers.f.half_carry || (!self.registers.f.subtract && (a & 0x0F) > 0x09)
+                {
+                    correction |= 0x06;
+                }
+                if carry || (!self.registers.f.subtract && a > 0x99) {
+                    correction |= 0x60;
+                    carry = true;
+                }
 
-                self.registers.f.zero = result == 0;
-                self.registers.f.subtract = false;
+                if self.registers.f.subtract {
+                    a = a.wrapping_sub(correction);
+                } else {
+                    a = a.wrapping_add(correction);
+                }
+
+                self.registers.a = a;
+                self.registers.f.zero = a == 0;
                 self.registers.f.half_carry = false;
-                self.registers.f.carry = false;
+                self.registers.f.carry = carry;
             }
 
-            Instruction::OR(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for OR!"),
-                };
-
-                let result = self.registers.a | value;
-                self.registers.f.zero = result == 0;
+            Instruction::SCF => {
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
-                self.registers.f.carry = false;
+                self.registers.f.carry = true;
             }
 
-            Instruction::AND(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    _ => panic!("Unknown target for AND!"),
-                };
-
-                let result = self.registers.a & value;
-                self.registers.f.zero = result == 0;
+            Instruction::CCF => {
                 self.registers.f.subtract = false;
+                self.registers.f.half_carry = false;
+                self.registers.f.carry = !self.registers.f.carry;
+            }
+
+            Instruction::CPL => {
+                self.registers.a = !self.registers.a;
+                self.registers.f.subtract = true;
                 self.registers.f.half_carry = true;
-                self.registers.f.carry = false;
             }
 
-            Instruction::LD(dest, source) => {
-                let load_value = match source {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    ArithmeticTarget::D8 => {
-                        let d8_val = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        d8_val
-                    }
-                    ArithmeticTarget::FFC => {
-                        let address = 0xFF00 + (self.registers.c as u16);
-                        bus.read_byte(address)
-                    }
-                    ArithmeticTarget::FFD8 => {
-                        let addr_byte = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        let address = 0xFF00 + (addr_byte as u16);
-                        bus.read_byte(address)
-                    }
-                    ArithmeticTarget::BC => bus.read_byte(self.registers.get_bc()),
-                    ArithmeticTarget::DE => bus.read_byte(self.registers.get_de()),
-                    ArithmeticTarget::D16 => {
-                        let address = bus.read_word(self.pc);
-                        self.pc = self.pc.wrapping_add(2);
-                        bus.read_byte(address)
-                    }
-                };
+            Instruction::JP(test) => {
+                let jump_addr = self.fetch_word(bus);
 
-                match dest {
-                    ArithmeticTarget::A => self.registers.a = load_value,
-                    ArithmeticTarget::B => self.registers.b = load_value,
-                    ArithmeticTarget::C => self.registers.c = load_value,
-                    ArithmeticTarget::D => self.registers.d = load_value,
-                    ArithmeticTarget::E => self.registers.e = load_value,
-                    ArithmeticTarget::H => self.registers.h = load_value,
-                    ArithmeticTarget::L => self.registers.l = load_value,
-                    ArithmeticTarget::HL => bus.write_byte(self.registers.get_hl(), load_value),
-                    ArithmeticTarget::FFC => {
-                        let address = 0xFF00 + (self.registers.c as u16);
-                        bus.write_byte(address, load_value);
-                    }
-                    ArithmeticTarget::FFD8 => {
-                        let addr_byte = bus.read_byte(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        let address = 0xFF00 + (addr_byte as u16);
-                        bus.write_byte(address, load_value);
-                    }
-                    ArithmeticTarget::BC => bus.write_byte(self.registers.get_bc(), load_value),
-                    ArithmeticTarget::DE => bus.write_byte(self.registers.get_de(), load_value),
-                    ArithmeticTarget::D16 => {
-                        let address = bus.read_word(self.pc);
-                        self.pc = self.pc.wrapping_add(1);
-                        bus.write_byte(address, load_value);
-                    }
-                    _ => panic!("Unknown WRITE target for LD!"),
+                if self.check_condition(test) {
+                    self.internal_delay(bus);
+                    self.pc = jump_addr;
                 }
             }
 
+            Instruction::JP_HL => self.pc = self.registers.get_hl(),
+
+            Instruction::XOR(target) => {
+                let value = self.read_reg(bus, &target);
+                let result = self.xor(value);
+                self.registers.a = result;
+            }
+
+            Instruction::OR(target) => {
+                let value = self.read_reg(bus, &target);
+                let result = self.or(value);
+                self.registers.a = result;
+            }
+
+            Instruction::AND(target) => {
+                let value = self.read_reg(bus, &target);
+                let result = self.and(value);
+                self.registers.a = result;
+            }
+
+            Instruction::LD(dest, source) => {
+                let load_value = self.read_reg(bus, &source);
+
+                self.write_reg(bus, dest, load_value);
+            }
+
             Instruction::LD16(target) => {
-                let value = bus.read_word(self.pc);
-                self.pc = self.pc.wrapping_add(2);
+                let value = self.fetch_word(bus);
 
                 match target {
                     Load16Target::BC => self.registers.set_bc(value),
@@ -365,6 +296,42 @@ impl CPU {
                 }
             }
 
+            Instruction::LD_D16_SP => {
+                let address = self.fetch_word(bus);
+                self.write_byte(bus, address, (self.sp & 0xFF) as u8);
+                self.write_byte(bus, address.wrapping_add(1), (self.sp >> 8) as u8);
+            }
+
+            Instruction::LD_HL_SP_E8 => {
+                let value = self.add_sp_e8(bus);
+                self.internal_delay(bus);
+                self.registers.set_hl(value);
+            }
+
+            Instruction::LD_SP_HL => {
+                self.internal_delay(bus);
+                self.sp = self.registers.get_hl();
+            }
+
+            Instruction::ADD_SP_E8 => {
+                let value = self.add_sp_e8(bus);
+                self.internal_delay(bus);
+                self.internal_delay(bus);
+                self.sp = value;
+            }
+
+            Instruction::ADDHL(target) => {
+                let value = match target {
+                    Load16Target::BC => self.registers.get_bc(),
+                    Load16Target::DE => self.registers.get_de(),
+                    Load16Target::HL => self.registers.get_hl(),
+                    Load16Target::SP => self.sp,
+                };
+                let result = self.add16(value);
+                self.internal_delay(bus);
+                self.registers.set_hl(result);
+            }
+
             Instruction::PUSH(target) => {
                 let value = match target {
                     StackTarget::BC => self.registers.get_bc(),
@@ -387,35 +354,29 @@ impl CPU {
             }
 
             Instruction::CALL(test) => {
-                let target_addr = bus.read_word(self.pc);
-                self.pc = self.pc.wrapping_add(2);
-                if match test {
-                    JumpTest::NotZero => !self.registers.f.zero,
-                    JumpTest::Zero => self.registers.f.zero,
-                    JumpTest::NotCarry => !self.registers.f.carry,
-                    JumpTest::Carry => self.registers.f.carry,
-                    JumpTest::Always => true,
-                } {
+                let target_addr = self.fetch_word(bus);
+                if self.check_condition(test) {
                     self.push(bus, self.pc);
                     self.pc = target_addr;
                 }
             }
 
             Instruction::RET(test) => {
-                if match test {
-                    JumpTest::NotZero => !self.registers.f.zero,
-                    JumpTest::Zero => self.registers.f.zero,
-                    JumpTest::NotCarry => !self.registers.f.carry,
-                    JumpTest::Carry => self.registers.f.carry,
-                    JumpTest::Always => true,
-                } {
+                // conditional returns spend an extra cycle evaluating the flags
+                if test != JumpTest::Always {
+                    self.internal_delay(bus);
+                }
+
+                if self.check_condition(test) {
                     let top = self.pop(bus);
+                    self.internal_delay(bus);
                     self.pc = top;
                 }
             }
 
             Instruction::RETI => {
                 let top = self.pop(bus);
+                self.internal_delay(bus);
                 self.pc = top;
                 self.ime = true;
             }
@@ -423,7 +384,7 @@ impl CPU {
             Instruction::LD_HL_DEC_A => {
                 let address = self.registers.get_hl();
                 let value = self.registers.a;
-                bus.write_byte(address, value);
+                self.write_byte(bus, address, value);
 
                 self.registers.set_hl(address.wrapping_sub(1));
             }
@@ -431,104 +392,78 @@ impl CPU {
             Instruction::LD_HL_INC_A => {
                 let address = self.registers.get_hl();
                 let value = self.registers.a;
-                bus.write_byte(address, value);
+                self.write_byte(bus, address, value);
                 self.registers.set_hl(address.wrapping_add(1));
             }
 
             Instruction::LD_A_HL_DEC => {
                 let address = self.registers.get_hl();
-                let value = bus.read_byte(address);
+                let value = self.read_byte(bus, address);
                 self.registers.a = value;
                 self.registers.set_hl(address.wrapping_sub(1));
             }
 
             Instruction::LD_A_HL_INC => {
                 let address = self.registers.get_hl();
-                let value = bus.read_byte(address);
+                let value = self.read_byte(bus, address);
                 self.registers.a = value;
                 self.registers.set_hl(address.wrapping_add(1));
             }
 
             Instruction::JR(test) => {
-                let offset = bus.read_byte(self.pc) as i8;
-                self.pc = self.pc.wrapping_add(1);
-                if match test {
-                    JumpTest::NotZero => !self.registers.f.zero,
-                    JumpTest::Zero => self.registers.f.zero,
-                    JumpTest::NotCarry => !self.registers.f.carry,
-                    JumpTest::Carry => self.registers.f.carry,
-                    JumpTest::Always => true,
-                } {
+                let offset = self.fetch_byte(bus) as i8;
+                if self.check_condition(test) {
+                    self.internal_delay(bus);
                     self.pc = self.pc.wrapping_add(offset as u16);
                 }
             }
 
             Instruction::INC(target) => {
-                let value = self.read_reg(&target);
-
-                let new_value = value.wrapping_add(1);
-
-                // println!(
-                //     "INC {:?} | Old: {} | New: {} | ZeroFlag: {}",
-                //     target,
-                //     value,
-                //     new_value,
-                //     new_value == 0,
-                // );
-                self.registers.f.zero = new_value == 0;
-                self.registers.f.subtract = false;
-                self.registers.f.half_carry = (value & 0xF) == 0xF;
-
-                self.write_reg(target, new_value);
+                let value = self.read_reg(bus, &target);
+                let new_value = self.inc(value);
+                self.write_reg(bus, target, new_value);
             }
 
             Instruction::DEC(target) => {
-                let value = self.read_reg(&target);
-                let new_value = value.wrapping_sub(1);
-                self.registers.f.zero = new_value == 0;
-                self.registers.f.subtract = true;
-                self.registers.f.half_carry = (value & 0xF) == 0;
-                self.write_reg(target, new_value);
-            }
-
-            Instruction::INC16(target) => match target {
-                Load16Target::BC => self
-                    .registers
-                    .set_bc(self.registers.get_bc().wrapping_add(1)),
-                Load16Target::DE => self
-                    .registers
-                    .set_de(self.registers.get_de().wrapping_add(1)),
-                Load16Target::HL => self
-                    .registers
-                    .set_hl(self.registers.get_hl().wrapping_add(1)),
-                Load16Target::SP => self.sp = self.sp.wrapping_add(1),
-            },
+                let value = self.read_reg(bus, &target);
+                let new_value = self.dec(value);
+                self.write_reg(bus, target, new_value);
+            }
 
-            Instruction::DEC16(target) => match target {
-                Load16Target::BC => self
-                    .registers
-                    .set_bc(self.registers.get_bc().wrapping_sub(1)),
-                Load16Target::DE => self
-                    .registers
-                    .set_de(self.registers.get_de().wrapping_sub(1)),
-                Load16Target::HL => self
-                    .registers
-                    .set_hl(self.registers.get_hl().wrapping_sub(1)),
-                Load16Target::SP => self.sp = self.sp.wrapping_sub(1),
-            },
+            Instruction::INC16(target) => {
+                self.internal_delay(bus);
+                match target {
+                    Load16Target::BC => self
+                        .registers
+                        .set_bc(self.registers.get_bc().wrapping_add(1)),
+                    Load16Target::DE => self
+                        .registers
+                        .set_de(self.registers.get_de().wrapping_add(1)),
+                    Load16Target::HL => self
+                        .registers
+                        .set_hl(self.registers.get_hl().wrapping_add(1)),
+                    Load16Target::SP => self.sp = self.sp.wrapping_add(1),
+                }
+            }
+
+            Instruction::DEC16(target) => {
+                self.internal_delay(bus);
+                match target {
+                    Load16Target::BC => self
+                        .registers
+                        .set_bc(self.registers.get_bc().wrapping_sub(1)),
+                    Load16Target::DE => self
+                        .registers
+                        .set_de(self.registers.get_de().wrapping_sub(1)),
+                    Load16Target::HL => self
+                        .registers
+                        .set_hl(self.registers.get_hl().wrapping_sub(1)),
+                    Load16Target::SP => self.sp = self.sp.wrapping_sub(1),
+                }
+            }
 
             Instruction::SWAP(target) => {
-                let value = match target {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                    ArithmeticTarget::HL => bus.read_byte(self.registers.get_hl()),
-                    _ => panic!("Unknown read target for SWAP!"),
-                };
+                let value = self.read_reg(bus, &target);
                 let top = (value & 0xF0) >> 4;
                 let bot = value & 0x0F;
                 let new_value = (bot << 4) | top;
@@ -538,39 +473,67 @@ impl CPU {
                 self.registers.f.half_carry = false;
                 self.registers.f.carry = false;
 
-                match target {
-                    ArithmeticTarget::A => self.registers.a = new_value,
-                    ArithmeticTarget::B => self.registers.b = new_value,
-                    ArithmeticTarget::C => self.registers.c = new_value,
-                    ArithmeticTarget::D => self.registers.d = new_value,
-                    ArithmeticTarget::E => self.registers.e = new_value,
-                    ArithmeticTarget::H => self.registers.h = new_value,
-                    ArithmeticTarget::L => self.registers.l = new_value,
-                    ArithmeticTarget::HL => bus.write_byte(self.registers.get_hl(), new_value),
-                    _ => panic!("Unknown write target for SWAP!"),
-                };
+                self.write_reg(bus, target, new_value);
             }
 
-            Instruction::BIT(target) => {
-                let value = match target {
-                    ArithmeticTarget::H => self.registers.h,
-                    _ => panic!("BIT target not implemented!"),
-                };
+            Instruction::BIT(bit, target) => {
+                let value = self.read_reg(bus, &target);
 
-                let result = value & 0x80;
+                let result = value & (1 << bit);
                 self.registers.f.zero = result == 0;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = true;
             }
 
-            Instruction::DI => self.ime = false,
-            Instruction::EI => self.ime = true,
+            Instruction::RES(bit, target) => {
+                let value = self.read_reg(bus, &target);
+                self.write_reg(bus, target, value & !(1 << bit));
+            }
+
+            Instruction::SET(bit, target) => {
+                let value = self.read_reg(bus, &target);
+                self.write_reg(bus, target, value | (1 << bit));
+            }
 
-            _ => {} // TODO: Support more instructions
+            Instruction::RST(address) => {
+                self.push(bus, self.pc);
+                self.pc = address;
+            }
+
+            Instruction::DI => {
+                self.ime = false;
+                self.ime_pending = false;
+            }
+
+            // EI only takes effect after the following instruction
+            Instruction::EI => self.ime_pending = true,
+
+            Instruction::HALT => {
+                // HALT bug: with IME off and an interrupt already pending the
+                // CPU never halts, and the next opcode byte is read twice
+                if !self.ime && self.interrupt_pending(bus) {
+                    self.halt_bug = true;
+                } else {
+                    self.halted = true;
+                }
+            }
+
+            Instruction::STOP => {
+                // STOP is followed by a padding byte that gets skipped
+                self.pc = self.pc.wrapping_add(1);
+                self.stopped = true;
+                bus.timer.reset_div();
+            }
+
+            Instruction::ILLEGAL => self.locked = true,
+
+            Instruction::NOP | Instruction::PREFIX => {}
         }
     }
 
-    fn read_reg(&self, target: &ArithmeticTarget) -> u8 {
+    // Reads an 8-bit operand. Memory operands and immediates cost one
+    // cycle per bus access, plus the cycles needed to fetch the address.
+    fn read_reg(&mut self, bus: &mut MemoryBus, target: &ArithmeticTarget) -> u8 {
         match target {
             ArithmeticTarget::A => self.registers.a,
             ArithmeticTarget::B => self.registers.b,
@@ -579,15 +542,23 @@ impl CPU {
             ArithmeticTarget::E => self.registers.e,
             ArithmeticTarget::H => self.registers.h,
             ArithmeticTarget::L => self.registers.l,
-            ArithmeticTarget::HL => {
-                println!("Reading from HL using read_reg!");
-                0x000000
+            ArithmeticTarget::HL => self.read_byte(bus, self.registers.get_hl()),
+            ArithmeticTarget::BC => self.read_byte(bus, self.registers.get_bc()),
+            ArithmeticTarget::DE => self.read_byte(bus, self.registers.get_de()),
+            ArithmeticTarget::D8 => self.fetch_byte(bus),
+            ArithmeticTarget::D16 => {
+                let address = self.fetch_word(bus);
+                self.read_byte(bus, address)
+            }
+            ArithmeticTarget::FFC => self.read_byte(bus, 0xFF00 + (self.registers.c as u16)),
+            ArithmeticTarget::FFD8 => {
+                let address = 0xFF00 + (self.fetch_byte(bus) as u16);
+                self.read_byte(bus, address)
             }
-            _ => panic!("Trying to read from unknown register!"),
         }
     }
 
-    fn write_reg(&mut self, target: ArithmeticTarget, value: u8) {
+    fn write_reg(&mut self, bus: &mut MemoryBus, target: ArithmeticTarget, value: u8) {
         match target {
             ArithmeticTarget::A => self.registers.a = value,
             ArithmeticTarget::B => self.registers.b = value,
@@ -596,11 +567,135 @@ impl CPU {
             ArithmeticTarget::E => self.registers.e = value,
             ArithmeticTarget::H => self.registers.h = value,
             ArithmeticTarget::L => self.registers.l = value,
-            ArithmeticTarget::HL => println!("Writing to HL register from write_reg!"),
-            _ => panic!("Trying to write to unknown register!"),
+            ArithmeticTarget::HL => self.write_byte(bus, self.registers.get_hl(), value),
+            ArithmeticTarget::BC => self.write_byte(bus, self.registers.get_bc(), value),
+            ArithmeticTarget::DE => self.write_byte(bus, self.registers.get_de(), value),
+            ArithmeticTarget::D16 => {
+                let address = self.fetch_word(bus);
+                self.write_byte(bus, address, value);
+            }
+            ArithmeticTarget::FFC => {
+                self.write_byte(bus, 0xFF00 + (self.registers.c as u16), value)
+            }
+            ArithmeticTarget::FFD8 => {
+                let address = 0xFF00 + (self.fetch_byte(bus) as u16);
+                self.write_byte(bus, address, value);
+            }
+            ArithmeticTarget::D8 => panic!("Trying to write to an immediate!"),
         }
     }
 
+    fn check_condition(&self, test: JumpTest) -> bool {
+        match test {
+            JumpTest::NotZero => !self.registers.f.zero,
+            JumpTest::Zero => self.registers.f.zero,
+            JumpTest::NotCarry => !self.registers.f.carry,
+            JumpTest::Carry => self.registers.f.carry,
+            JumpTest::Always => true,
+        }
+    }
+
+    // Every bus access takes one M-cycle (4 T-cycles), and so does
+    // each idle cycle an instruction spends on internal work.
+    // In M-cycle stepped mode the rest of the system is advanced before
+    // each access, so reads and writes land on the right cycle.
+    fn m_cycle(&mut self, bus: &mut MemoryBus) {
+        self.cycles += T_CYCLES_PER_M_CYCLE;
+        if M_CYCLE_STEPPED {
+            bus.tick(T_CYCLES_PER_M_CYCLE);
+        }
+    }
+
+    fn internal_delay(&mut self, bus: &mut MemoryBus) {
+        self.m_cycle(bus);
+    }
+
+    fn read_byte(&mut self, bus: &mut MemoryBus, address: u16) -> u8 {
+        self.m_cycle(bus);
+        bus.read_byte(address)
+    }
+
+    fn write_byte(&mut self, bus: &mut MemoryBus, address: u16, value: u8) {
+        self.m_cycle(bus);
+        bus.write_byte(address, value);
+    }
+
+    fn fetch_byte(&mut self, bus: &mut MemoryBus) -> u8 {
+        let value = self.read_byte(bus, self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        value
+    }
+
+    // little endian, low byte first
+    fn fetch_word(&mut self, bus: &mut MemoryBus) -> u16 {
+        let low = self.fetch_byte(bus) as u16;
+        let high = self.fetch_byte(bus) as u16;
+        (high << 8) | low
+    }
+
+    // flags shared by the CB rotate and shift instructions
+    fn set_shift_flags(&mut self, result: u8, carry: bool) {
+        self.registers.f.zero = result == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = carry;
+    }
+
+    // INC and DEC leave the carry flag alone
+    fn inc(&mut self, value: u8) -> u8 {
+        let new_value = value.wrapping_add(1);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = (value & 0xF) == 0xF;
+        new_value
+    }
+
+    fn dec(&mut self, value: u8) -> u8 {
+        let new_value = value.wrapping_sub(1);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = true;
+        self.registers.f.half_carry = (value & 0xF) == 0;
+        new_value
+    }
+
+    fn and(&mut self, value: u8) -> u8 {
+        let result = self.registers.a & value;
+        self.registers.f.zero = result == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = true;
+        self.registers.f.carry = false;
+        result
+    }
+
+    fn or(&mut self, value: u8) -> u8 {
+        let result = self.registers.a | value;
+        self.registers.f.zero = result == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = false;
+        result
+    }
+
+    fn xor(&mut self, value: u8) -> u8 {
+        let result = self.registers.a ^ value;
+        self.registers.f.zero = result == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = false;
+        result
+    }
+
+    // ADD HL,rr: zero flag is left untouched, half carry comes from bit 11
+    // and carry from bit 15
+    fn add16(&mut self, value: u16) -> u16 {
+        let hl = self.registers.get_hl();
+        let (result, overflow) = hl.overflowing_add(value);
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = (hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF;
+        self.registers.f.carry = overflow;
+        result
+    }
+
     fn add(&mut self, value: u8) -> u8 {
         let (new_value, overflow) = self.registers.a.overflowing_add(value);
         self.registers.f.zero = new_value == 0;
@@ -649,69 +744,124 @@ impl CPU {
         result
     }
 
+    // SP + signed immediate, shared by ADD SP,e8 and LD HL,SP+e8.
+    // H and C come from the unsigned add of the low byte.
+    fn add_sp_e8(&mut self, bus: &mut MemoryBus) -> u16 {
+        let offset = self.fetch_byte(bus);
+
+        let sp = self.sp;
+        let value = offset as i8 as i16 as u16;
+
+        self.registers.f.zero = false;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = (sp & 0x0F) + (value & 0x0F) > 0x0F;
+        self.registers.f.carry = (sp & 0xFF) + (value & 0xFF) > 0xFF;
+
+        sp.wrapping_add(value)
+    }
+
+    // PUSH spends one internal cycle decrementing SP before writing,
+    // high byte first
     fn push(&mut self, bus: &mut MemoryBus, value: u16) {
-        self.sp = self.sp.wrapping_sub(2);
-        bus.write_word(self.sp, value);
+        self.internal_delay(bus);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_byte(bus, self.sp, (value >> 8) as u8);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_byte(bus, self.sp, (value & 0xFF) as u8);
     }
 
     fn pop(&mut self, bus: &mut MemoryBus) -> u16 {
-        let top = bus.read_word(self.sp);
-        self.sp = self.sp.wrapping_add(2);
-        top
+        let low = self.read_byte(bus, self.sp) as u16;
+        self.sp = self.sp.wrapping_add(1);
+        let high = self.read_byte(bus, self.sp) as u16;
+        self.sp = self.sp.wrapping_add(1);
+        (high << 8) | low
     }
 
-    pub fn step(&mut self, bus: &mut MemoryBus) {
-        let instruction_byte = bus.read_byte(self.pc);
-        self.pc = self.pc.wrapping_add(1);
-
-        let instruction = Instruction::from_byte(instruction_byte);
-
-        match instruction {
-            Some(Instruction::PREFIX) => {
-                let cb_byte = bus.read_byte(self.pc);
-                self.pc = self.pc.wrapping_add(1);
-
-                if let Some(cb_inst) = Instruction::from_cb_byte(cb_byte) {
-                    self.execute(cb_inst, bus);
-                } else {
-                    println!("Program Counter: {}", self.pc);
-                    panic!("Unknown CB Instruction: 0xCB{:02x}", cb_byte);
-                }
+    // Executes one instruction, or dispatches a pending interrupt, and
+    // returns how many T-cycles it took. The bus has been advanced by the
+    // same amount when this returns. During STOP nothing runs and this
+    // returns 0.
+    pub fn step(&mut self, bus: &mut MemoryBus) -> u32 {
+        self.cycles = 0;
+
+        if self.locked {
+            self.internal_delay(bus);
+        } else if self.stopped {
+            // the timer and PPU are frozen too, only a joypad press brings
+            // the system out of STOP
+            if bus.interrupts.flag & Interrupt::Joypad.bit() != 0 {
+                self.stopped = false;
             }
-            Some(instr) => self.execute(instr, bus),
-            None => {
-                println!("Program Counter: {}", self.pc);
-                panic!("Unknown instruction from byte: 0x{:02X}", instruction_byte)
+        } else if self.halted {
+            // HALT ends as soon as IE & IF is non-zero, even with IME off
+            self.internal_delay(bus);
+            if self.interrupt_pending(bus) {
+                self.halted = false;
+            }
+        } else if !self.handle_interrupts(bus) {
+            if self.ime_pending {
+                self.ime = true;
+                self.ime_pending = false;
             }
+            self.fetch_and_execute(bus);
         }
-    }
 
-    pub fn handle_interrupts(&mut self, bus: &mut MemoryBus) {
-        if !self.ime {
-            return;
+        if !M_CYCLE_STEPPED {
+            bus.tick(self.cycles);
         }
 
-        let ie = bus.read_byte(0xFFFF);
-        let if_reg = bus.read_byte(0xFF0F);
-        let pending = ie & if_reg;
+        self.cycles
+    }
 
-        if pending > 0 {
-            // VBLANK
-            if pending & 0x01 != 0 {
-                self.service_interrupt(bus, 0, 0x0040);
-            }
-        }
+    fn fetch_and_execute(&mut self, bus: &mut MemoryBus) {
+        let instruction_byte = if self.halt_bug {
+            self.halt_bug = false;
+            self.read_byte(bus, self.pc)
+        } else {
+            self.fetch_byte(bus)
+        };
+        let instruction = match Instruction::from_byte(instruction_byte) {
+            Instruction::PREFIX => Instruction::from_cb_byte(self.fetch_byte(bus)),
+            instruction => instruction,
+        };
+        self.execute(instruction, bus);
     }
 
-    fn service_interrupt(&mut self, bus: &mut MemoryBus, interrupt_bit: u8, addr: u16) {
-        self.ime = false;
-        let mut if_reg = bus.read_byte(0xFF0F);
+    fn interrupt_pending(&self, bus: &MemoryBus) -> bool {
+        bus.interrupts.pending().is_some()
+    }
 
-        if_reg &= !(1 << interrupt_bit);
-        bus.write_byte(0xFF0F, if_reg);
+    // Interrupt dispatch takes 5 M-cycles: two idle cycles, the PC push
+    // and one more cycle to load the vector.
+    fn handle_interrupts(&mut self, bus: &mut MemoryBus) -> bool {
+        if !self.ime || !self.interrupt_pending(bus) {
+            return false;
+        }
 
-        self.push(bus, self.pc);
-        self.pc = addr;
+        self.ime = false;
+        self.internal_delay(bus);
+        self.internal_delay(bus);
+
+        // The vector is only picked after the high byte of PC is pushed. If
+        // that write lands on IE and clears the pending bit, the dispatch is
+        // cancelled and execution continues at 0x0000 instead.
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_byte(bus, self.sp, (self.pc >> 8) as u8);
+        let interrupt = bus.interrupts.pending();
+        self.sp = self.sp.wrapping_sub(1);
+        self.write_byte(bus, self.sp, (self.pc & 0xFF) as u8);
+
+        self.pc = match interrupt {
+            Some(interrupt) => {
+                bus.interrupts.acknowledge(interrupt);
+                interrupt.vector()
+            }
+            None => 0x0000,
+        };
+        self.internal_delay(bus);
+
+        true
     }
 }
 
@@ -806,3 +956,541 @@ impl std::convert::From<u8> for FlagsRegister {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joypad::Button;
+
+    const Z: u8 = 0x80;
+    const N: u8 = 0x40;
+    const H: u8 = 0x20;
+    const C: u8 = 0x10;
+
+    // A CPU about to run `program` from WRAM, with the stack in WRAM too
+    fn load(program: &[u8]) -> (CPU, MemoryBus) {
+        let mut bus = MemoryBus::new(Model::DMG);
+        for (i, byte) in program.iter().enumerate() {
+            bus.write_byte(0xC000 + i as u16, *byte);
+        }
+
+        let mut cpu = CPU::new();
+        cpu.pc = 0xC000;
+        cpu.sp = 0xDFF0;
+        (cpu, bus)
+    }
+
+    // Runs a single instruction placed in WRAM and returns the CPU afterwards
+    fn run(program: &[u8], setup: impl FnOnce(&mut CPU)) -> CPU {
+        let (mut cpu, mut bus) = load(program);
+        setup(&mut cpu);
+        cpu.step(&mut bus);
+        cpu
+    }
+
+    fn run_alu(opcode: u8, a: u8, b: u8, f: u8) -> (u8, u8) {
+        let cpu = run(&[opcode], |cpu| {
+            cpu.registers.a = a;
+            cpu.registers.b = b;
+            cpu.registers.f = FlagsRegister::from(f);
+        });
+        (cpu.registers.a, cpu.registers.f.into())
+    }
+
+    // (opcode, A, B, F in, A out, F out)
+    const ALU_CASES: &[(u8, u8, u8, u8, u8, u8)] = &[
+        // ADD A,B
+        (0x80, 0x3A, 0xC6, 0, 0x00, Z | H | C),
+        (0x80, 0x3C, 0xFF, 0, 0x3B, H | C),
+        (0x80, 0x3C, 0x12, Z | N | H | C, 0x4E, 0),
+        (0x80, 0x0F, 0x01, 0, 0x10, H),
+        // ADC A,B
+        (0x88, 0xE1, 0x0F, C, 0xF1, H),
+        (0x88, 0xE1, 0x3B, C, 0x1D, C),
+        (0x88, 0xE1, 0x1E, C, 0x00, Z | H | C),
+        (0x88, 0x0F, 0x00, C, 0x10, H),
+        // SUB B
+        (0x90, 0x3E, 0x3E, 0, 0x00, Z | N),
+        (0x90, 0x3E, 0x0F, 0, 0x2F, N | H),
+        (0x90, 0x3E, 0x40, 0, 0xFE, N | C),
+        // SBC A,B
+        (0x98, 0x3B, 0x2A, C, 0x10, N),
+        (0x98, 0x3B, 0x3A, C, 0x00, Z | N),
+        (0x98, 0x3B, 0x4F, C, 0xEB, N | H | C),
+        (0x98, 0x10, 0x00, C, 0x0F, N | H),
+        // CP B leaves A alone
+        (0xB8, 0x3C, 0x2F, 0, 0x3C, N | H),
+        (0xB8, 0x3C, 0x3C, 0, 0x3C, Z | N),
+        (0xB8, 0x3C, 0x40, 0, 0x3C, N | C),
+        // AND / OR / XOR B
+        (0xA0, 0x5A, 0x3F, C, 0x1A, H),
+        (0xA0, 0x5A, 0x00, 0, 0x00, Z | H),
+        (0xB0, 0x5A, 0x0F, N | H | C, 0x5F, 0),
+        (0xB0, 0x00, 0x00, 0, 0x00, Z),
+        (0xA8, 0xFF, 0xFF, N | H | C, 0x00, Z),
+        (0xA8, 0xFF, 0x0F, 0, 0xF0, 0),
+        // INC A / DEC A keep C
+        (0x3C, 0xFF, 0x00, C, 0x00, Z | H | C),
+        (0x3C, 0x50, 0x00, N, 0x51, 0),
+        (0x3D, 0x01, 0x00, 0, 0x00, Z | N),
+        (0x3D, 0x00, 0x00, C, 0xFF, N | H | C),
+        // CPL, SCF, CCF
+        (0x2F, 0x35, 0x00, Z, 0xCA, Z | N | H),
+        (0x37, 0x00, 0x00, Z | N | H, 0x00, Z | C),
+        (0x3F, 0x00, 0x00, N | H | C, 0x00, 0),
+        (0x3F, 0x00, 0x00, Z, 0x00, Z | C),
+        // DAA after ADD and after SUB
+        (0x27, 0x7D, 0x00, 0, 0x83, 0),
+        (0x27, 0x4B, 0x00, N | H, 0x45, N),
+        (0x27, 0x9A, 0x00, 0, 0x00, Z | C),
+        (0x27, 0x00, 0x00, N | C, 0xA0, N | C),
+        // rotates on A always clear Z
+        (0x07, 0x85, 0x00, Z, 0x0B, C),
+        (0x0F, 0x3B, 0x00, 0, 0x9D, C),
+        (0x17, 0x95, 0x00, C, 0x2B, C),
+        (0x1F, 0x81, 0x00, 0, 0x40, C),
+        (0x07, 0x00, 0x00, 0, 0x00, 0),
+    ];
+
+    #[test]
+    fn alu_flag_table() {
+        for &(opcode, a, b, f, expected_a, expected_f) in ALU_CASES {
+            let (result_a, result_f) = run_alu(opcode, a, b, f);
+            assert_eq!(
+                (result_a, result_f),
+                (expected_a, expected_f),
+                "opcode {:#04x} with A={:#04x} B={:#04x} F={:#04x}",
+                opcode,
+                a,
+                b,
+                f
+            );
+        }
+    }
+
+    fn run_cb(opcode: u8, b: u8, f: u8) -> (u8, u8) {
+        let cpu = run(&[0xCB, opcode], |cpu| {
+            cpu.registers.b = b;
+            cpu.registers.f = FlagsRegister::from(f);
+        });
+        (cpu.registers.b, cpu.registers.f.into())
+    }
+
+    // (CB opcode on B, B, F in, B out, F out)
+    const CB_CASES: &[(u8, u8, u8, u8, u8)] = &[
+        // RLC / RRC B rotate through bit 7 and bit 0, setting Z
+        (0x00, 0x85, 0, 0x0B, C),
+        (0x00, 0x00, C, 0x00, Z),
+        (0x08, 0x01, 0, 0x80, C),
+        (0x08, 0x02, C, 0x01, 0),
+        // RL / RR B shift the old carry in
+        (0x10, 0x80, C, 0x01, C),
+        (0x10, 0x80, 0, 0x00, Z | C),
+        (0x10, 0x11, C, 0x23, 0),
+        (0x18, 0x01, C, 0x80, C),
+        (0x18, 0x01, 0, 0x00, Z | C),
+        (0x18, 0x8A, N | H, 0x45, 0),
+        // SLA / SRA / SRL B, SRA keeps bit 7 and SRL clears it
+        (0x20, 0xFF, 0, 0xFE, C),
+        (0x20, 0x80, 0, 0x00, Z | C),
+        (0x28, 0x8A, C, 0xC5, 0),
+        (0x28, 0x81, 0, 0xC0, C),
+        (0x28, 0x01, 0, 0x00, Z | C),
+        (0x38, 0x8A, C, 0x45, 0),
+        (0x38, 0x81, 0, 0x40, C),
+        (0x38, 0x01, 0, 0x00, Z | C),
+        // SWAP B clears C
+        (0x30, 0xF1, N | H | C, 0x1F, 0),
+        (0x30, 0x00, 0, 0x00, Z),
+        // BIT n,B sets Z from the bit and H, clears N and keeps C
+        (0x40, 0x01, N | C, 0x01, H | C),
+        (0x40, 0xFE, 0, 0xFE, Z | H),
+        (0x58, 0x08, Z, 0x08, H),
+        (0x58, 0xF7, C, 0xF7, Z | H | C),
+        (0x68, 0x20, 0, 0x20, H),
+        (0x68, 0xDF, N, 0xDF, Z | H),
+        (0x78, 0x7F, 0, 0x7F, Z | H),
+        (0x78, 0x80, C, 0x80, H | C),
+        // RES / SET n,B leave the flags alone
+        (0x98, 0xFF, Z | N | H | C, 0xF7, Z | N | H | C),
+        (0x80, 0x01, 0, 0x00, 0),
+        (0xF0, 0x00, 0, 0x40, 0),
+        (0xF8, 0x7F, C, 0xFF, C),
+    ];
+
+    #[test]
+    fn cb_result_table() {
+        for &(opcode, b, f, expected_b, expected_f) in CB_CASES {
+            assert_eq!(
+                run_cb(opcode, b, f),
+                (expected_b, expected_f),
+                "CB {:#04x} with B={:#04x} F={:#04x}",
+                opcode,
+                b,
+                f
+            );
+        }
+    }
+
+    #[test]
+    fn cb_hl_operands_write_back_through_the_bus() {
+        // RES 1,(HL); SET 6,(HL); RLC (HL); BIT 6,(HL)
+        let (mut cpu, mut bus) = load(&[0xCB, 0x8E, 0xCB, 0xF6, 0xCB, 0x06, 0xCB, 0x76]);
+        cpu.registers.set_hl(0xC100);
+        bus.write_byte(0xC100, 0x83);
+
+        cpu.step(&mut bus);
+        assert_eq!(bus.read_byte(0xC100), 0x81);
+        cpu.step(&mut bus);
+        assert_eq!(bus.read_byte(0xC100), 0xC1);
+        cpu.step(&mut bus);
+        assert_eq!(bus.read_byte(0xC100), 0x83);
+        assert_eq!(u8::from(cpu.registers.f), C);
+        cpu.step(&mut bus);
+        assert_eq!(bus.read_byte(0xC100), 0x83);
+        assert_eq!(u8::from(cpu.registers.f), Z | H | C);
+    }
+
+    // Checks every operand combination of the 8-bit adders against flags
+    // derived from the carry-out of each bit position
+    #[test]
+    fn exhaustive_add_sub_flags() {
+        for a in 0..=0xFFu16 {
+            for b in 0..=0xFFu16 {
+                for carry in 0..=1u16 {
+                    let f_in = if carry == 1 { C } else { 0 };
+
+                    let sum = a + b + carry;
+                    let r = sum as u8;
+                    let h = (a ^ b ^ sum) & 0x10 != 0;
+                    let expected = (if r == 0 { Z } else { 0 })
+                        | (if h { H } else { 0 })
+                        | (if sum > 0xFF { C } else { 0 });
+                    assert_eq!(run_alu(0x88, a as u8, b as u8, f_in), (r, expected));
+
+                    let diff = (a as i16) - (b as i16) - (carry as i16);
+                    let r = diff as u8;
+                    let h = (a as i16 ^ b as i16 ^ diff) & 0x10 != 0;
+                    let expected = N
+                        | (if r == 0 { Z } else { 0 })
+                        | (if h { H } else { 0 })
+                        | (if diff < 0 { C } else { 0 });
+                    assert_eq!(run_alu(0x98, a as u8, b as u8, f_in), (r, expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn daa_produces_bcd_results() {
+        let to_bcd = |n: u8| ((n / 10) << 4) | (n % 10);
+
+        for x in 0..100u8 {
+            for y in 0..100u8 {
+                let (sum, f) = run_alu(0x80, to_bcd(x), to_bcd(y), 0);
+                let (bcd, f) = run_alu(0x27, sum, 0, f);
+                assert_eq!(bcd, to_bcd((x + y) % 100), "{} + {}", x, y);
+                assert_eq!(f & C != 0, x + y >= 100, "{} + {}", x, y);
+
+                let (diff, f) = run_alu(0x90, to_bcd(x), to_bcd(y), 0);
+                let (bcd, f) = run_alu(0x27, diff, 0, f);
+                let expected = (x as i16 - y as i16).rem_euclid(100) as u8;
+                assert_eq!(bcd, to_bcd(expected), "{} - {}", x, y);
+                assert_eq!(f & C != 0, x < y, "{} - {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn add_hl_keeps_zero_and_carries_from_bit_11() {
+        let cpu = run(&[0x09], |cpu| {
+            cpu.registers.set_hl(0x8A23);
+            cpu.registers.set_bc(0x0605);
+            cpu.registers.f = FlagsRegister::from(Z);
+        });
+        assert_eq!(cpu.registers.get_hl(), 0x9028);
+        assert_eq!(u8::from(cpu.registers.f), Z | H);
+
+        let cpu = run(&[0x29], |cpu| cpu.registers.set_hl(0x8A23));
+        assert_eq!(cpu.registers.get_hl(), 0x1446);
+        assert_eq!(u8::from(cpu.registers.f), H | C);
+    }
+
+    #[test]
+    fn sp_relative_adds_carry_from_low_byte() {
+        let cpu = run(&[0xE8, 0x02], |cpu| cpu.sp = 0xFFF8);
+        assert_eq!(cpu.sp, 0xFFFA);
+        assert_eq!(u8::from(cpu.registers.f), 0);
+
+        let cpu = run(&[0xE8, 0xFF], |cpu| {
+            cpu.sp = 0x0001;
+            cpu.registers.f = FlagsRegister::from(Z | N);
+        });
+        assert_eq!(cpu.sp, 0x0000);
+        assert_eq!(u8::from(cpu.registers.f), H | C);
+
+        let cpu = run(&[0xF8, 0x02], |cpu| cpu.sp = 0xFFF8);
+        assert_eq!(cpu.registers.get_hl(), 0xFFFA);
+        assert_eq!(u8::from(cpu.registers.f), 0);
+    }
+
+    // (program, F, T-cycles), conditional ones both taken and not taken
+    const TIMING_CASES: &[(&[u8], u8, u32)] = &[
+        (&[0x00], 0, 4),
+        // JR NZ,e / JP NZ,a16 / CALL NZ,a16 / RET NZ
+        (&[0x20, 0x05], 0, 12),
+        (&[0x20, 0x05], Z, 8),
+        (&[0xC2, 0x00, 0xC1], 0, 16),
+        (&[0xC2, 0x00, 0xC1], Z, 12),
+        (&[0xC4, 0x00, 0xC1], 0, 24),
+        (&[0xC4, 0x00, 0xC1], Z, 12),
+        (&[0xC0], 0, 20),
+        (&[0xC0], Z, 8),
+        // unconditional forms
+        (&[0x18, 0x05], 0, 12),
+        (&[0xC3, 0x00, 0xC1], 0, 16),
+        (&[0xCD, 0x00, 0xC1], 0, 24),
+        (&[0xC9], 0, 16),
+        (&[0xD9], 0, 16),
+        (&[0xE9], 0, 4),
+        (&[0xFF], 0, 16),
+        // BIT only reads (HL), RES and SET write it back
+        (&[0xCB, 0x46], 0, 12),
+        (&[0xCB, 0x86], 0, 16),
+        (&[0xCB, 0xC6], 0, 16),
+        (&[0xCB, 0x06], 0, 16),
+        (&[0xCB, 0x40], 0, 8),
+        // PUSH BC / POP BC / LD (a16),SP
+        (&[0xC5], 0, 16),
+        (&[0xC1], 0, 12),
+        (&[0x08, 0x00, 0xC1], 0, 20),
+        // LD (HL),d8 / INC (HL) / LD HL,SP+e / ADD SP,e / INC BC
+        (&[0x36, 0x12], 0, 12),
+        (&[0x34], 0, 12),
+        (&[0xF8, 0x01], 0, 12),
+        (&[0xE8, 0x01], 0, 16),
+        (&[0x03], 0, 8),
+        // LDH (a8),A / LD A,(a16)
+        (&[0xE0, 0x80], 0, 12),
+        (&[0xFA, 0x00, 0xC1], 0, 16),
+    ];
+
+    #[test]
+    fn instruction_timing_table() {
+        for &(program, f, expected) in TIMING_CASES {
+            let (mut cpu, mut bus) = load(program);
+            cpu.registers.set_hl(0xC100);
+            cpu.registers.f = FlagsRegister::from(f);
+            assert_eq!(
+                cpu.step(&mut bus),
+                expected,
+                "{:02x?} with F={:#04x}",
+                program,
+                f
+            );
+        }
+    }
+
+    #[test]
+    fn interrupt_dispatch_takes_20_cycles() {
+        let (mut cpu, mut bus) = load(&[0x00]);
+        cpu.ime = true;
+        bus.interrupts.write_enable(0x01);
+        bus.interrupts.request(Interrupt::VBlank);
+
+        assert_eq!(cpu.step(&mut bus), 20);
+        assert_eq!(cpu.pc, 0x0040);
+    }
+
+    #[cfg(feature = "mcycle")]
+    #[test]
+    fn memory_reads_land_on_their_m_cycle() {
+        // LD A,(0xFF05) reads TIMA on its fourth M-cycle
+        let (mut cpu, mut bus) = load(&[0xFA, 0x05, 0xFF]);
+        bus.write_byte(0xFF05, 0x10);
+        bus.write_byte(0xFF07, 0x05);
+        // TIMA increments 12 T-cycles in, during the operand fetches
+        bus.timer.set_counter(0x0004);
+
+        assert_eq!(cpu.step(&mut bus), 16);
+        assert_eq!(cpu.registers.a, 0x11);
+    }
+
+    #[test]
+    fn halt_ends_on_pending_interrupt_without_ime() {
+        // HALT, INC A
+        let (mut cpu, mut bus) = load(&[0x76, 0x3C]);
+        bus.interrupts.write_enable(0x04);
+
+        cpu.step(&mut bus);
+        assert!(cpu.halted);
+        cpu.step(&mut bus);
+        assert!(cpu.halted);
+
+        // with IME off the interrupt isn't serviced, execution just resumes
+        bus.interrupts.request(Interrupt::Timer);
+        cpu.step(&mut bus);
+        assert!(!cpu.halted);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.pc, 0xC002);
+        assert_eq!(
+            bus.interrupts.flag & Interrupt::Timer.bit(),
+            Interrupt::Timer.bit()
+        );
+    }
+
+    #[test]
+    fn halt_bug_reads_the_next_byte_twice() {
+        // HALT, INC A, NOP
+        let (mut cpu, mut bus) = load(&[0x76, 0x3C, 0x00]);
+        bus.interrupts.write_enable(0x04);
+        bus.interrupts.request(Interrupt::Timer);
+
+        cpu.step(&mut bus);
+        assert!(!cpu.halted);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0xC001);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.registers.a, 2);
+        assert_eq!(cpu.pc, 0xC002);
+    }
+
+    #[test]
+    fn stop_freezes_the_system_until_a_joypad_press() {
+        // STOP, padding, INC A
+        let (mut cpu, mut bus) = load(&[0x10, 0x00, 0x3C]);
+        bus.write_byte(0xFF07, 0x05);
+        bus.write_byte(0xFF40, 0x91);
+        bus.write_byte(0xFF00, 0x10);
+
+        cpu.step(&mut bus);
+        assert!(cpu.stopped);
+        let div = bus.read_byte(0xFF04);
+        let tima = bus.read_byte(0xFF05);
+        for _ in 0..10_000 {
+            assert_eq!(cpu.step(&mut bus), 0);
+        }
+        assert_eq!(bus.read_byte(0xFF04), div);
+        assert_eq!(bus.read_byte(0xFF05), tima);
+        assert_eq!(bus.ppu.ly, 0);
+
+        // a d-pad press isn't selected, a button press is
+        bus.set_button(Button::Up, true);
+        cpu.step(&mut bus);
+        assert!(cpu.stopped);
+        bus.set_button(Button::A, true);
+        cpu.step(&mut bus);
+        assert!(!cpu.stopped);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.pc, 0xC003);
+    }
+
+    #[test]
+    fn illegal_opcodes_lock_the_cpu() {
+        for opcode in [
+            0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+        ] {
+            let (mut cpu, mut bus) = load(&[opcode, 0x3C]);
+            bus.interrupts.write_enable(0x01);
+            bus.interrupts.request(Interrupt::VBlank);
+            cpu.step(&mut bus);
+            assert!(cpu.locked, "{:#04x}", opcode);
+
+            // nothing runs afterwards, not even interrupts
+            cpu.ime = true;
+            assert_eq!(cpu.step(&mut bus), 4);
+            assert_eq!(cpu.pc, 0xC001);
+            assert_eq!(cpu.registers.a, 0);
+        }
+    }
+
+    #[test]
+    fn ei_takes_effect_after_the_next_instruction() {
+        // EI, INC A, INC A
+        let (mut cpu, mut bus) = load(&[0xFB, 0x3C, 0x3C]);
+        bus.interrupts.write_enable(0x01);
+        bus.interrupts.request(Interrupt::VBlank);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!((cpu.registers.a, cpu.pc), (1, 0xC002));
+        cpu.step(&mut bus);
+        assert_eq!((cpu.registers.a, cpu.pc), (1, 0x0040));
+        assert_eq!(bus.interrupts.flag, 0);
+        assert!(!cpu.ime);
+    }
+
+    #[test]
+    fn pushing_pc_into_ie_can_cancel_dispatch() {
+        // with SP at 0x0000 the high byte of PC lands on IE
+        let (mut cpu, mut bus) = load(&[0x00]);
+        cpu.ime = true;
+        cpu.sp = 0x0000;
+        bus.interrupts.write_enable(0x01);
+        bus.interrupts.request(Interrupt::VBlank);
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0000);
+        assert_eq!(bus.interrupts.read_enable(), 0xC0);
+        assert_eq!(bus.interrupts.flag, 0x01);
+
+        // a high byte that keeps the bit set still dispatches
+        let (mut cpu, mut bus) = load(&[0x00]);
+        cpu.ime = true;
+        cpu.sp = 0x0000;
+        cpu.pc = 0x01C0;
+        bus.interrupts.write_enable(0x01);
+        bus.interrupts.request(Interrupt::VBlank);
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0040);
+        assert_eq!(bus.interrupts.flag, 0x00);
+    }
+
+    #[test]
+    fn skip_boot_state_per_model() {
+        // model, AF, BC, DE, HL, NR52, STAT, DIV
+        let cases = [
+            (
+                Model::DMG0,
+                0x0100,
+                0xFF13,
+                0x00C1,
+                0x8403,
+                0xF1,
+                0x81,
+                0x18,
+            ),
+            (Model::DMG, 0x01B0, 0x0013, 0x00D8, 0x014D, 0xF1, 0x85, 0xAB),
+            (Model::MGB, 0xFFB0, 0x0013, 0x00D8, 0x014D, 0xF1, 0x85, 0xAB),
+            (Model::SGB, 0x0100, 0x0014, 0x0000, 0xC060, 0xF0, 0x85, 0x00),
+            (Model::CGB, 0x1180, 0x0000, 0xFF56, 0x000D, 0xF1, 0x85, 0x26),
+        ];
+
+        for (model, af, bc, de, hl, nr52, stat, div) in cases {
+            let mut cpu = CPU::new();
+            let mut bus = MemoryBus::new(model);
+            cpu.skip_boot(model, 0x4D);
+            bus.skip_boot();
+
+            let registers = &cpu.registers;
+            assert_eq!(
+                (
+                    registers.get_af(),
+                    registers.get_bc(),
+                    registers.get_de(),
+                    registers.get_hl()
+                ),
+                (af, bc, de, hl),
+                "{:?}",
+                model
+            );
+            assert_eq!((cpu.pc, cpu.sp), (0x0100, 0xFFFE));
+
+            let io = [0xFF26, 0xFF40, 0xFF41, 0xFF44, 0xFF04, 0xFF0F].map(|a| bus.read_byte(a));
+            assert_eq!(io, [nr52, 0x91, stat, 0x00, div, 0xE1], "{:?}", model);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+pub const OAM_SIZE: usize = 0xA0;
+
+#[derive(Clone, Copy)]
+struct Transfer {
+    source: u16,
+    index: usize,
+}
+
+// OAM DMA (0xFF46) copies 160 bytes from XX00 into OAM, one per M-cycle.
+// A write takes one M-cycle to set up; if a transfer is already running
+// it keeps going during that cycle and is then replaced.
+pub struct Dma {
+    pub register: u8,
+    // written this M-cycle, and set up during the last one
+    pending: Option<u8>,
+    starting: Option<u8>,
+    transfer: Option<Transfer>,
+    // the byte the DMA last put on the bus, which is what the CPU sees
+    // when it reads from the same bus
+    pub bus_value: u8,
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Dma {
+            register: 0xFF,
+            pending: None,
+            starting: None,
+            transfer: None,
+            bus_value: 0xFF,
+        }
+    }
+
+    pub fn start(&mut self, byte: u8) {
+        self.register = byte;
+        self.pending = Some(byte);
+    }
+
+    // Address the running transfer reads from
+    pub fn source(&self) -> Option<u16> {
+        self.transfer.map(|t| t.source + t.index as u16)
+    }
+
+    // Advances by one M-cycle. Returns the source address and OAM index
+    // of the byte to copy this cycle, if any.
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        if let Some(page) = self.starting.take() {
+            self.transfer = Some(Transfer {
+                source: (page as u16) << 8,
+                index: 0,
+            });
+        }
+        self.starting = self.pending.take();
+
+        let transfer = self.transfer.as_mut()?;
+        let copy = (transfer.source + transfer.index as u16, transfer.index);
+        transfer.index += 1;
+        if transfer.index == OAM_SIZE {
+            self.transfer = None;
+        }
+        Some(copy)
+    }
+}
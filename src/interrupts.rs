@@ -0,0 +1,109 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    // highest priority first, matching the bit order in IE/IF
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    pub fn vector(self) -> u16 {
+        0x0040 + (self as u16) * 8
+    }
+}
+
+// IE (0xFFFF) and IF (0xFF0F)
+pub struct InterruptController {
+    pub enable: u8,
+    pub flag: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        InterruptController {
+            enable: 0x00,
+            flag: 0x00,
+        }
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flag |= interrupt.bit();
+    }
+
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flag &= !interrupt.bit();
+    }
+
+    // highest priority interrupt that is both requested and enabled
+    pub fn pending(&self) -> Option<Interrupt> {
+        let pending = self.enable & self.flag;
+        Interrupt::ALL
+            .into_iter()
+            .find(|interrupt| pending & interrupt.bit() != 0)
+    }
+
+    // only the low 5 bits of IF exist, the rest read back as 1
+    pub fn read_flag(&self) -> u8 {
+        self.flag | 0xE0
+    }
+
+    pub fn write_flag(&mut self, byte: u8) {
+        self.flag = byte & 0x1F;
+    }
+
+    // IE is a full 8-bit register, the upper bits are just never serviced
+    pub fn read_enable(&self) -> u8 {
+        self.enable
+    }
+
+    pub fn write_enable(&mut self, byte: u8) {
+        self.enable = byte;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priority_masking_and_unused_bits() {
+        let mut interrupts = InterruptController::new();
+        assert_eq!(interrupts.read_flag(), 0xE0);
+
+        interrupts.request(Interrupt::Joypad);
+        interrupts.request(Interrupt::Timer);
+        interrupts.request(Interrupt::VBlank);
+        assert_eq!(interrupts.read_flag(), 0xE0 | 0x15);
+        assert_eq!(interrupts.pending(), None);
+
+        // only enabled requests count, lowest bit first
+        interrupts.write_enable(0xFE);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Timer));
+        interrupts.write_enable(0xFF);
+        assert_eq!(interrupts.pending(), Some(Interrupt::VBlank));
+        interrupts.acknowledge(Interrupt::VBlank);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Timer));
+        assert_eq!(Interrupt::Timer.vector(), 0x0050);
+
+        // IF drops its upper bits, IE keeps all eight
+        interrupts.write_flag(0xFF);
+        assert_eq!(interrupts.flag, 0x1F);
+        interrupts.write_enable(0xE0);
+        assert_eq!(interrupts.read_enable(), 0xE0);
+        assert_eq!(interrupts.pending(), None);
+    }
+}
@@ -0,0 +1,103 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+// P1/JOYP (0xFF00). Bits 4 and 5 select the d-pad or the buttons, the low
+// nibble reads back the selected lines. Everything is active low.
+pub struct Joypad {
+    select: u8,
+    dpad: u8,
+    buttons: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            select: 0x30,
+            dpad: 0x0F,
+            buttons: 0x0F,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & 0x10 == 0 {
+            lines &= self.dpad;
+        }
+        if self.select & 0x20 == 0 {
+            lines &= self.buttons;
+        }
+
+        0xC0 | self.select | lines
+    }
+
+    pub fn write(&mut self, byte: u8) {
+        self.select = byte & 0x30;
+    }
+
+    // Returns true when a selected line goes from high to low, which is
+    // what raises the joypad interrupt
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> bool {
+        let before = self.read();
+
+        let (lines, bit) = match button {
+            Button::Right => (&mut self.dpad, 0),
+            Button::Left => (&mut self.dpad, 1),
+            Button::Up => (&mut self.dpad, 2),
+            Button::Down => (&mut self.dpad, 3),
+            Button::A => (&mut self.buttons, 0),
+            Button::B => (&mut self.buttons, 1),
+            Button::Select => (&mut self.buttons, 2),
+            Button::Start => (&mut self.buttons, 3),
+        };
+
+        if pressed {
+            *lines &= !(1 << bit);
+        } else {
+            *lines |= 1 << bit;
+        }
+
+        before & !self.read() & 0x0F != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_lines_pick_the_button_group() {
+        let mut joypad = Joypad::new();
+        joypad.set_button(Button::Down, true);
+        joypad.set_button(Button::A, true);
+        assert_eq!(joypad.read(), 0xFF);
+
+        joypad.write(0x20);
+        assert_eq!(joypad.read(), 0xE7);
+        joypad.write(0x10);
+        assert_eq!(joypad.read(), 0xDE);
+        joypad.write(0x00);
+        assert_eq!(joypad.read(), 0xC6);
+
+        joypad.set_button(Button::Down, false);
+        assert_eq!(joypad.read(), 0xCE);
+    }
+
+    #[test]
+    fn presses_interrupt_only_on_selected_lines() {
+        let mut joypad = Joypad::new();
+        joypad.write(0x20);
+        assert!(!joypad.set_button(Button::Start, true));
+        assert!(joypad.set_button(Button::Left, true));
+        assert!(!joypad.set_button(Button::Left, true));
+        assert!(!joypad.set_button(Button::Left, false));
+    }
+}
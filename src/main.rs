@@ -1,45 +1,181 @@
+#![allow(clippy::upper_case_acronyms)]
+
+mod apu;
 mod bus;
+mod cartridge;
+mod config;
 mod cpu;
+mod dma;
 mod instruction;
+mod interrupts;
+mod joypad;
+mod mbc;
+mod model;
+mod palette;
 mod ppu;
+mod save;
+mod serial;
+mod timer;
 
 use bus::MemoryBus;
+use cartridge::Cartridge;
+use config::Config;
 use cpu::CPU;
-use minifb::{Window, WindowOptions};
+use joypad::Button;
+use minifb::{Key, Window, WindowOptions};
+use model::Model;
+use palette::Palette;
 use ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use save::SaveFile;
+use std::cell::Cell;
 use std::error::Error;
 use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+const KEY_MAP: [(Key, Button); 8] = [
+    (Key::Right, Button::Right),
+    (Key::Left, Button::Left),
+    (Key::Up, Button::Up),
+    (Key::Down, Button::Down),
+    (Key::X, Button::A),
+    (Key::Z, Button::B),
+    (Key::Backspace, Button::Select),
+    (Key::Enter, Button::Start),
+];
+
+struct Options {
+    rom_path: String,
+    boot_rom_path: Option<String>,
+    model: Model,
+    skip_boot: bool,
+    config_path: Option<String>,
+    palette: Option<Palette>,
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut bus = MemoryBus::new();
-    let mut cpu = CPU::new();
+// usage: dmg01 [--skip-boot] [--model dmg0|dmg|mgb|sgb|cgb] [--boot-rom <path>]
+//              [--config <path>] [--palette gray|green|pocket|light|<colors>] [rom]
+fn parse_args() -> Result<Options, Box<dyn Error>> {
+    let mut options = Options {
+        rom_path: String::from("Tetris (World) (Rev 1).gb"),
+        boot_rom_path: None,
+        model: Model::DMG,
+        skip_boot: false,
+        config_path: None,
+        palette: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--skip-boot" => options.skip_boot = true,
+            "--model" => {
+                let name = args.next().ok_or("--model needs a value")?;
+                options.model =
+                    Model::from_name(&name).ok_or(format!("unknown model '{}'", name))?;
+            }
+            "--boot-rom" => {
+                options.boot_rom_path = Some(args.next().ok_or("--boot-rom needs a path")?);
+            }
+            "--config" => {
+                options.config_path = Some(args.next().ok_or("--config needs a path")?);
+            }
+            "--palette" => {
+                let value = args.next().ok_or("--palette needs a value")?;
+                options.palette =
+                    Some(Palette::parse(&value).ok_or(format!("unknown palette '{}'", value))?);
+            }
+            _ => options.rom_path = arg,
+        }
+    }
 
-    let bootrom: Vec<u8> = fs::read("dmg_boot.bin")?;
+    Ok(options)
+}
 
-    let gamerom = fs::read("Tetris (World) (Rev 1).gb").unwrap_or_else(|_| {
-        println!("Warning: could not find gamerom, loading dummy rom!");
-        vec![0; 0x8000]
-    });
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = parse_args()?;
+
+    // a config file only has to exist when asked for explicitly
+    let config = match &options.config_path {
+        Some(path) => Config::load(path)?,
+        None if Path::new(config::DEFAULT_PATH).exists() => Config::load(config::DEFAULT_PATH)?,
+        None => Config::default(),
+    };
+
+    let mut bus = MemoryBus::new(options.model);
+    bus.ppu.shades = options
+        .palette
+        .or(config.palette)
+        .unwrap_or(Palette::Gray)
+        .shades();
+    let mut cpu = CPU::new();
 
-    let game_header = &gamerom[0..0x100];
-    let mut bootrom_finished = false;
+    let gamerom = fs::read(&options.rom_path)
+        .map_err(|e| format!("could not read {}: {}", options.rom_path, e))?;
+    let mut cartridge = Cartridge::new(gamerom)?;
 
-    for (i, byte) in gamerom.iter().enumerate() {
-        if i < 0x10000 {
-            bus.write_byte(i as u16, *byte);
-        }
+    if let Err(e) = cartridge.verify_header_checksum() {
+        println!("Warning: {}", e);
+    }
+    if let Err(e) = cartridge.verify_global_checksum() {
+        println!("Warning: {}", e);
     }
 
-    for (i, byte) in bootrom.iter().enumerate() {
-        bus.write_byte(i as u16, *byte);
+    let mut save_file = cartridge
+        .header
+        .has_battery()
+        .then(|| SaveFile::new(&options.rom_path));
+    if let Some(save_file) = &save_file
+        && save_file.load(&mut cartridge)?
+    {
+        println!("Loaded save from {}", save_file.path().display());
     }
 
+    let bootrom = if options.skip_boot {
+        Vec::new()
+    } else {
+        let path = options
+            .boot_rom_path
+            .as_deref()
+            .unwrap_or(options.model.boot_rom_name());
+        fs::read(path).unwrap_or_else(|_| {
+            println!("Warning: could not find boot ROM {}, skipping boot!", path);
+            Vec::new()
+        })
+    };
+
     println!(
-        "System loaded. BootROM: {} bytes | GameROM: {} bytes",
+        "System loaded. Model: {:?} | BootROM: {} bytes | GameROM: {} bytes",
+        options.model,
         bootrom.len(),
-        gamerom.len()
+        cartridge.header.rom_size
+    );
+    println!(
+        "Cartridge: \"{}\" | Type: {:#04x} | ROM: {} KiB | RAM: {} KiB | Version: {}",
+        cartridge.header.title,
+        cartridge.header.cartridge_type,
+        cartridge.header.rom_size / 1024,
+        cartridge.header.ram_size / 1024,
+        cartridge.header.version
     );
 
+    if bootrom.is_empty() {
+        cpu.skip_boot(options.model, cartridge.header.header_checksum);
+        bus.skip_boot();
+    } else {
+        bus.load_boot_rom(bootrom);
+    }
+
+    // no rumble hardware here, so the window title shows the motor state
+    let rumble = Rc::new(Cell::new(false));
+    let motor = rumble.clone();
+    cartridge.set_rumble_callback(Box::new(move |on| motor.set(on)));
+    let mut rumble_shown = false;
+
+    bus.load_cartridge(cartridge);
+
     let mut window = Window::new(
         "Gameboy",
         SCREEN_WIDTH,
@@ -48,83 +184,58 @@ fn main() -> Result<(), Box<dyn Error>> {
     )
     .unwrap();
 
-    let mut executed_count: u32 = 0;
-
-    println!("FF50 (boot disable) = {:#04x}", bus.read_byte(0xFF50));
-
-    let mut dumped = false;
-
-    let mut scanline_counter = 0;
-
     while window.is_open() {
-        // --- TRACE START ---
-        // Kept your trace filter so logs don't explode.
-        // This only logs the critical handover from Boot ROM to Tetris.
-        let opcode = bus.read_byte(cpu.pc);
-        // println!(
-        //     "PC: {:#04x} | Op: {:#02x} | SP: {:#04x} | A: {:#02x} | B: {:#02x} | HL: {:#04x} | FZ: {}",
-        //     cpu.pc,
-        //     opcode,
-        //     cpu.sp,
-        //     cpu.registers.a,
-        //     cpu.registers.b,
-        //     cpu.registers.get_hl(),
-        //     cpu.registers.f.zero,
-        // );
-        // --- TRACE END ---
-
-        cpu.handle_interrupts(&mut bus);
         cpu.step(&mut bus);
-        executed_count += 1;
-        scanline_counter += 1;
-        if bus.ppu.ly == 144 {
-            // Only print once per frame to avoid spamming the console
-            let opcode = bus.read_byte(cpu.pc);
-            // Peek at the NEXT byte (the operand) to see what we are comparing against
-            let operand = bus.read_byte(cpu.pc.wrapping_add(1));
-            //
-            // println!(
-            //     "LY=144 HIT! | PC:{:#04x} | Op:{:#02x} | Operand:{:#02x} | A:{:#02x} | Z-Flag:{}",
-            //     cpu.pc,
-            //     opcode,
-            //     operand,
-            //     cpu.registers.a,
-            //     cpu.registers.f.zero
-            // );
-        }
 
-        if !bootrom_finished && bus.boot_enabled {
-            println!("Unmapping BootROM, restoring GameROM header...");
-            for i in 0..0x100 {
-                bus.write_byte(i as u16, game_header[i]);
+        // the PPU is frozen during STOP, so keep polling input to wake it
+        if bus.ppu.frame_ready || cpu.stopped {
+            bus.ppu.frame_ready = false;
+            if cpu.stopped {
+                thread::sleep(Duration::from_millis(16));
             }
-            bootrom_finished = true;
-        }
 
-        if scanline_counter >= 114 {
-            scanline_counter = 0;
+            window
+                .update_with_buffer(&bus.ppu.buffer, SCREEN_WIDTH, SCREEN_HEIGHT)
+                .unwrap();
+
+            for (key, button) in KEY_MAP {
+                bus.set_button(button, window.is_key_down(key));
+            }
 
-            let vblank_triggered = bus.ppu.tick(
-                bus.read_byte(0xFF40),
-                bus.read_byte(0xFF43),
-                bus.read_byte(0xFF42),
-            );
+            // IJKL tilt carts with an accelerometer by one g
+            let axis = |negative, positive| {
+                window.is_key_down(positive) as i8 as f32
+                    - window.is_key_down(negative) as i8 as f32
+            };
+            if let Some(cartridge) = &mut bus.cartridge {
+                cartridge.set_tilt(axis(Key::J, Key::L), axis(Key::I, Key::K));
+            }
 
-            if vblank_triggered {
-                let mut if_reg = bus.read_byte(0xFF0F);
-                if_reg |= 1;
-                bus.write_byte(0xFF0F, if_reg);
+            if let (Some(save_file), Some(cartridge)) = (&mut save_file, &mut bus.cartridge)
+                && let Err(e) = save_file.update(cartridge)
+            {
+                println!(
+                    "Warning: could not write {}: {}",
+                    save_file.path().display(),
+                    e
+                );
+            }
 
-                window
-                    .update_with_buffer(&bus.ppu.buffer, SCREEN_WIDTH, SCREEN_HEIGHT)
-                    .unwrap();
+            if rumble.get() != rumble_shown {
+                rumble_shown = rumble.get();
+                window.set_title(if rumble_shown {
+                    "Gameboy (rumble)"
+                } else {
+                    "Gameboy"
+                });
             }
         }
+    }
 
-        if executed_count > 200_000 && !dumped {
-            bus.ppu.dump_vram();
-            dumped = true;
-        }
+    if let (Some(save_file), Some(cartridge)) = (&mut save_file, &bus.cartridge) {
+        save_file
+            .flush(cartridge)
+            .map_err(|e| format!("could not write {}: {}", save_file.path().display(), e))?;
     }
 
     Ok(())
@@ -0,0 +1,114 @@
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, banked_offset, store};
+
+const IR_MODE: u8 = 0x0E;
+
+// Hudson's HuC1. Much like MBC1 without the banking modes, but the RAM
+// enable register can instead switch 0xA000 over to an infrared port.
+// No IR partner is emulated, so the receiver never sees light and the
+// LED goes nowhere.
+pub struct HuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl HuC1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        HuC1 {
+            rom,
+            ram: vec![0; ram_size],
+            ir_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        banked_offset(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for HuC1 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = byte & 0x0F == IR_MODE,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x03,
+            _ => {}
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            // bit 0 set would mean light was received
+            return 0xC0;
+        }
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if self.ir_mode || self.ram.is_empty() {
+            return false;
+        }
+        let offset = self.ram_offset(address);
+        store(&mut self.ram[offset], byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_banking_and_ir_mode() {
+        let mut rom = vec![0; 8 * ROM_BANK_SIZE];
+        for bank in 0..8 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let mut mbc = HuC1::new(rom, 4 * RAM_BANK_SIZE);
+
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        // unlike MBC1, bank 0 can be mapped at 0x4000
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0);
+
+        mbc.write_rom(0x4000, 0x02);
+        assert!(mbc.write_ram(0xA010, 0x42));
+        assert_eq!(mbc.read_ram(0xA010), 0x42);
+
+        // the IR port hides RAM and drops writes
+        mbc.write_rom(0x0000, 0x0E);
+        assert_eq!(mbc.read_ram(0xA010), 0xC0);
+        assert!(!mbc.write_ram(0xA010, 0x99));
+        mbc.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA010), 0x42);
+    }
+}
@@ -0,0 +1,216 @@
+use super::rtc::unix_time;
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, banked_offset, store};
+
+const MINUTES_PER_DAY: u16 = 1440;
+const SAVE_SIZE: usize = 17;
+
+// Nibble addresses in the clock chip's memory
+const MINUTES: usize = 0x00;
+const DAYS: usize = 0x03;
+const ALARM_MINUTES: usize = 0x58;
+const ALARM_DAYS: usize = 0x5B;
+const ALARM_ENABLED: usize = 0x5F;
+
+// Hudson's HuC3. The RAM enable register doubles as a mode select that
+// decides what 0xA000..=0xBFFF talks to: RAM, the command port of the
+// clock chip, its response port, a semaphore, or the infrared port.
+pub struct HuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+    // the clock chip's nibble-wide memory, addressed by commands
+    memory: [u8; 0x100],
+    access_index: u8,
+    response: u8,
+    // host time the minute and day counters correspond to
+    timestamp: u64,
+}
+
+impl HuC3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        HuC3 {
+            rom,
+            ram: vec![0; ram_size],
+            mode: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            memory: [0; 0x100],
+            access_index: 0,
+            response: 0,
+            timestamp: unix_time(),
+        }
+    }
+
+    fn read_field(&self, start: usize, nibbles: usize) -> u16 {
+        (0..nibbles).fold(0, |value, i| {
+            value | (self.memory[start + i] as u16) << (i * 4)
+        })
+    }
+
+    fn write_field(&mut self, start: usize, nibbles: usize, value: u16) {
+        for i in 0..nibbles {
+            self.memory[start + i] = (value >> (i * 4)) as u8 & 0x0F;
+        }
+    }
+
+    // Folds the host time that passed into the minute and day counters,
+    // keeping the leftover seconds for next time
+    fn update_clock(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.timestamp) / 60;
+        self.timestamp += elapsed * 60;
+
+        let minutes = self.read_field(MINUTES, 3) as u64 + elapsed;
+        let days = self.read_field(DAYS, 4) as u64 + minutes / MINUTES_PER_DAY as u64;
+        self.write_field(MINUTES, 3, (minutes % MINUTES_PER_DAY as u64) as u16);
+        self.write_field(DAYS, 4, days as u16);
+    }
+
+    // Returns true when the command changed the saved memory
+    fn command(&mut self, byte: u8) -> bool {
+        self.update_clock(unix_time());
+        let index = self.access_index as usize;
+        let argument = byte & 0x0F;
+
+        match byte >> 4 {
+            0x1 => {
+                self.response = self.memory[index];
+                self.access_index = self.access_index.wrapping_add(1);
+            }
+            0x2 => return store(&mut self.memory[index], argument),
+            0x3 => {
+                self.access_index = self.access_index.wrapping_add(1);
+                return store(&mut self.memory[index], argument);
+            }
+            0x4 => self.access_index = (self.access_index & 0xF0) | argument,
+            0x5 => self.access_index = (self.access_index & 0x0F) | argument << 4,
+            _ => {}
+        }
+        false
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        banked_offset(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for HuC3 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    // Same layout as SameBoy: 64-bit timestamp, minutes, days, alarm
+    // minutes and alarm days as 16-bit words, then the alarm flag
+    fn clock_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(SAVE_SIZE);
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data.extend_from_slice(&self.read_field(MINUTES, 3).to_le_bytes());
+        data.extend_from_slice(&self.read_field(DAYS, 4).to_le_bytes());
+        data.extend_from_slice(&self.read_field(ALARM_MINUTES, 3).to_le_bytes());
+        data.extend_from_slice(&self.read_field(ALARM_DAYS, 4).to_le_bytes());
+        data.push(self.memory[ALARM_ENABLED]);
+        data
+    }
+
+    fn load_clock_data(&mut self, data: &[u8]) {
+        if data.len() < SAVE_SIZE {
+            return;
+        }
+        let word = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+
+        self.timestamp = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        self.write_field(MINUTES, 3, word(8));
+        self.write_field(DAYS, 4, word(10));
+        self.write_field(ALARM_MINUTES, 3, word(12));
+        self.write_field(ALARM_DAYS, 4, word(14));
+        self.memory[ALARM_ENABLED] = data[16] & 0x0F;
+        self.update_clock(unix_time());
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.mode = byte & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x03,
+            _ => {}
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            0x00 | 0x0A if !self.ram.is_empty() => self.ram[self.ram_offset(address)],
+            0x0C => 0x80 | self.response,
+            // the clock chip is always ready
+            0x0D => 0x01,
+            // no IR partner is emulated, so no light is ever received
+            0x0E => 0xC0,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        match self.mode {
+            0x0A if !self.ram.is_empty() => {
+                let offset = self.ram_offset(address);
+                store(&mut self.ram[offset], byte)
+            }
+            0x0B => self.command(byte),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_commands_and_catch_up() {
+        let mut mbc = HuC3::new(vec![0; 4 * ROM_BANK_SIZE], 0x2000);
+        mbc.write_rom(0x0000, 0x0B);
+        // point at the minute counter and write 1439 (23:59) nibble by nibble
+        mbc.write_ram(0xA000, 0x40);
+        mbc.write_ram(0xA000, 0x50);
+        for nibble in [0xF, 0x9, 0x5] {
+            mbc.write_ram(0xA000, 0x30 | nibble);
+        }
+        assert_eq!(mbc.read_field(MINUTES, 3), 1439);
+
+        let data = mbc.clock_data();
+        assert_eq!(data.len(), SAVE_SIZE);
+        let timestamp = mbc.timestamp;
+
+        let mut restored = HuC3::new(vec![0; 4 * ROM_BANK_SIZE], 0x2000);
+        restored.load_clock_data(&data);
+        restored.timestamp = timestamp;
+        restored.update_clock(timestamp + 61);
+        assert_eq!(restored.read_field(MINUTES, 3), 0);
+        assert_eq!(restored.read_field(DAYS, 4), 1);
+
+        // read the low minute nibble back through the response port
+        restored.write_rom(0x0000, 0x0B);
+        restored.write_ram(0xA000, 0x40);
+        restored.write_ram(0xA000, 0x50);
+        restored.write_ram(0xA000, 0x10);
+        restored.write_rom(0x0000, 0x0C);
+        assert_eq!(restored.read_ram(0xA000), 0x80);
+    }
+}
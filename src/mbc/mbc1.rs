@@ -0,0 +1,192 @@
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, banked_offset, store};
+
+const MULTICART_SIZE: usize = 0x100000;
+const LOGO: std::ops::Range<usize> = 0x0104..0x0134;
+
+// MBC1 splits the bank number over two registers: BANK1 holds the low
+// 5 bits and BANK2 holds 2 more bits that select either the upper ROM
+// bits or the RAM bank. MBC1M multicarts leave the top bit of BANK1
+// unconnected, so BANK2 starts at bit 4 and picks one of four games.
+pub struct MBC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: u8,
+    multicart: bool,
+}
+
+impl MBC1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        let multicart = Self::is_multicart(&rom);
+        MBC1 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: 0,
+            multicart,
+        }
+    }
+
+    // Multicarts are 1 MiB and carry a second copy of the Nintendo logo
+    // in the header of the game at bank 0x10
+    fn is_multicart(rom: &[u8]) -> bool {
+        if rom.len() != MULTICART_SIZE {
+            return false;
+        }
+
+        let second = 0x10 * ROM_BANK_SIZE;
+        rom[LOGO] == rom[second + LOGO.start..second + LOGO.end]
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn low_bank(&self) -> usize {
+        if self.mode == 0 {
+            return 0;
+        }
+        (self.bank2 << self.bank2_shift()) as usize
+    }
+
+    fn high_bank(&self) -> usize {
+        let bank1 = if self.multicart {
+            self.bank1 & 0x0F
+        } else {
+            self.bank1
+        };
+        ((self.bank2 << self.bank2_shift()) | bank1) as usize
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank = if self.mode == 0 { 0 } else { self.bank2 };
+        banked_offset(&self.ram, RAM_BANK_SIZE, bank as usize, address)
+    }
+}
+
+impl Mapper for MBC1 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => self.low_bank(),
+            _ => self.high_bank(),
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // bank 0 can't be selected here, the zero check looks at
+                // all 5 bits even on multicarts
+                self.bank1 = byte & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = byte & 0x03,
+            _ => self.mode = byte & 0x01,
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return false;
+        }
+        let offset = self.ram_offset(address);
+        store(&mut self.ram[offset], byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every bank starts with its own bank number
+    fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn bank_zero_maps_to_one() {
+        let mut mbc = MBC1::new(numbered_rom(8), 0);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        // the bank number wraps to the ROM size
+        mbc.write_rom(0x2000, 0x0B);
+        assert_eq!(mbc.read_rom(0x4000), 3);
+        // but the zero check happens before masking
+        mbc.write_rom(0x2000, 0x10);
+        assert_eq!(mbc.read_rom(0x4000), 0);
+    }
+
+    #[test]
+    fn upper_bits_and_modes() {
+        let mut mbc = MBC1::new(numbered_rom(128), 0x8000);
+        mbc.write_rom(0x2000, 0x02);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_rom(0x4000), 0x42);
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+
+        // mode 1 applies BANK2 to the low area and to RAM
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x40);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x12);
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn multicart_uses_four_bit_bank1() {
+        let mut rom = numbered_rom(64);
+        for game in 0..4 {
+            let base = game * 0x10 * ROM_BANK_SIZE;
+            rom[base + LOGO.start..base + LOGO.end].fill(0xCE);
+        }
+
+        let mut mbc = MBC1::new(rom, 0);
+        assert!(mbc.multicart);
+        mbc.write_rom(0x2000, 0x12);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x10);
+    }
+}
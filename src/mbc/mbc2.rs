@@ -0,0 +1,100 @@
+use super::{Mapper, ROM_BANK_SIZE, banked_offset, store};
+
+const RAM_SIZE: usize = 0x200;
+
+// MBC2 decodes its two registers from address bit 8 instead of address
+// ranges, and carries 512 half-bytes of RAM on the chip itself. The RAM
+// only has 9 address lines, so it repeats across all of 0xA000..=0xBFFF.
+pub struct MBC2 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl MBC2 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        MBC2 {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Mapper for MBC2 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x3FFF if address & 0x0100 == 0 => {
+                self.ram_enabled = byte & 0x0F == 0x0A;
+            }
+            0x0000..=0x3FFF => {
+                self.rom_bank = byte & 0x0F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    // Only the low nibble exists, the upper one reads as open bus
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        0xF0 | self.ram[address as usize & (RAM_SIZE - 1)]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        let offset = address as usize & (RAM_SIZE - 1);
+        self.ram_enabled && store(&mut self.ram[offset], byte & 0x0F)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_select_and_ram_echo() {
+        let mut rom = vec![0; 16 * ROM_BANK_SIZE];
+        rom[3 * ROM_BANK_SIZE] = 3;
+        let mut mbc = MBC2::new(rom);
+
+        // bit 8 clear writes the RAM enable, not the bank
+        mbc.write_rom(0x2000, 0x03);
+        assert_eq!(mbc.read_rom(0x4000), 0);
+        mbc.write_rom(0x2100, 0x03);
+        assert_eq!(mbc.read_rom(0x4000), 3);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA005, 0xAB);
+        assert_eq!(mbc.read_ram(0xA005), 0xFB);
+        assert_eq!(mbc.read_ram(0xA205), 0xFB);
+        assert_eq!(mbc.read_ram(0xBE05), 0xFB);
+    }
+}
@@ -0,0 +1,143 @@
+use super::rtc::{self, Rtc};
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, banked_offset, store};
+
+// MBC3 has a 7-bit ROM bank register, up to 4 RAM banks and, on the
+// TIMER variants, a real-time clock whose registers are selected through
+// the RAM bank register.
+pub struct MBC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    // 0x00-0x03 select a RAM bank, 0x08-0x0C an RTC register
+    ram_select: u8,
+}
+
+impl MBC3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rtc: bool) -> Self {
+        MBC3 {
+            rom,
+            ram: vec![0; ram_size],
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_select: 0,
+        }
+    }
+}
+
+impl Mapper for MBC3 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn clock_data(&self) -> Vec<u8> {
+        self.rtc.as_ref().map(Rtc::save).unwrap_or_default()
+    }
+
+    fn load_clock_data(&mut self, data: &[u8]) {
+        if let Some(rtc) = &mut self.rtc
+            && data.len() >= rtc::SAVE_SIZE - 4
+        {
+            rtc.load(data);
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = byte & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_select = byte & 0x0F,
+            _ => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(byte);
+                }
+            }
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match (self.ram_select, &self.rtc) {
+            (0x00..=0x03, _) if !self.ram.is_empty() => {
+                self.ram[banked_offset(&self.ram, RAM_BANK_SIZE, self.ram_select as usize, address)]
+            }
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_select),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        match (self.ram_select, &mut self.rtc) {
+            (0x00..=0x03, _) if !self.ram.is_empty() => {
+                let offset =
+                    banked_offset(&self.ram, RAM_BANK_SIZE, self.ram_select as usize, address);
+                store(&mut self.ram[offset], byte)
+            }
+            // the clock registers are saved after the RAM
+            (0x08..=0x0C, Some(rtc)) => {
+                rtc.write(self.ram_select, byte);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_select_routes_to_rtc_or_ram() {
+        let mut mbc = MBC3::new(vec![0; 4 * ROM_BANK_SIZE], 0x8000, true);
+        mbc.write_rom(0x0000, 0x0A);
+
+        // halt the clock so the test doesn't race the host time
+        mbc.write_rom(0x4000, 0x0C);
+        mbc.write_ram(0xA000, 0x40);
+        mbc.write_rom(0x4000, 0x09);
+        mbc.write_ram(0xA000, 42);
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 42);
+
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(0xA010, 0x99);
+        assert_eq!(mbc.ram[2 * RAM_BANK_SIZE + 0x10], 0x99);
+        assert_eq!(mbc.read_ram(0xA010), 0x99);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA010), 0xFF);
+    }
+}
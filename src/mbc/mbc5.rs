@@ -0,0 +1,139 @@
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, RumbleCallback, banked_offset, store};
+
+const RUMBLE_MOTOR: u8 = 0x08;
+
+// MBC5 has a 9-bit ROM bank split over two registers and, unlike the
+// older mappers, lets bank 0 be mapped at 0x4000. On rumble carts bit 3
+// of the RAM bank register drives the motor instead of addressing RAM.
+pub struct MBC5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    has_rumble: bool,
+    motor_on: bool,
+    rumble_callback: Option<RumbleCallback>,
+}
+
+impl MBC5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rumble: bool) -> Self {
+        MBC5 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            motor_on: false,
+            rumble_callback: None,
+        }
+    }
+
+    fn set_motor(&mut self, on: bool) {
+        if on == self.motor_on {
+            return;
+        }
+        self.motor_on = on;
+        if let Some(callback) = &mut self.rumble_callback {
+            callback(on);
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        banked_offset(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for MBC5 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+        self.rumble_callback = Some(callback);
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | byte as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((byte & 0x01) as u16) << 8,
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.ram_bank = byte & 0x07;
+                self.set_motor(byte & RUMBLE_MOTOR != 0);
+            }
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x0F,
+            _ => {}
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return false;
+        }
+        let offset = self.ram_offset(address);
+        store(&mut self.ram[offset], byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        let mut rom = vec![0; 512 * ROM_BANK_SIZE];
+        rom[0x100 * ROM_BANK_SIZE] = 0xAA;
+        let mut mbc = MBC5::new(rom, 0, false);
+
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0x00);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0xAA);
+    }
+
+    #[test]
+    fn rumble_reports_motor_changes() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut mbc = MBC5::new(vec![0; 4 * ROM_BANK_SIZE], 0x8000, true);
+        let log = events.clone();
+        mbc.set_rumble_callback(Box::new(move |on| log.borrow_mut().push(on)));
+
+        mbc.write_rom(0x4000, 0x09);
+        mbc.write_rom(0x4000, 0x0B);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(*events.borrow(), vec![true, false]);
+
+        // the motor bit doesn't take part in RAM banking
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x55);
+        assert_eq!(mbc.ram[2 * RAM_BANK_SIZE], 0x55);
+    }
+}
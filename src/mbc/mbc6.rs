@@ -0,0 +1,234 @@
+use super::{Mapper, banked_offset, store};
+
+const ROM_WINDOW_SIZE: usize = 0x2000;
+const RAM_WINDOW_SIZE: usize = 0x1000;
+const FLASH_SIZE: usize = 0x20000;
+const FLASH_SECTOR_SIZE: usize = 0x2000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlashState {
+    Idle,
+    Unlocked1,
+    Unlocked2,
+    Program,
+    EraseArmed,
+    EraseUnlocked1,
+    EraseUnlocked2,
+}
+
+// MBC6 (Net de Get) splits both address ranges into two independently
+// banked halves: 8 KiB ROM windows at 0x4000 and 0x6000 that can each
+// show ROM or the 128 KiB flash chip, and 4 KiB RAM windows at 0xA000
+// and 0xB000. The flash lives after the SRAM in `ram` so both end up in
+// the save file.
+pub struct MBC6 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    sram_size: usize,
+    ram_enabled: bool,
+    ram_banks: [u8; 2],
+    rom_banks: [u8; 2],
+    flash_selected: [bool; 2],
+    flash_enabled: bool,
+    flash_write_enabled: bool,
+    flash_state: FlashState,
+}
+
+impl MBC6 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        MBC6 {
+            rom,
+            ram: vec![0; ram_size + FLASH_SIZE],
+            sram_size: ram_size,
+            ram_enabled: false,
+            ram_banks: [0; 2],
+            rom_banks: [0; 2],
+            flash_selected: [false; 2],
+            flash_enabled: false,
+            flash_write_enabled: false,
+            flash_state: FlashState::Idle,
+        }
+    }
+
+    fn window(address: u16) -> usize {
+        ((address >> 13) & 0x01) as usize
+    }
+
+    fn flash_offset(&self, window: usize, address: u16) -> usize {
+        (self.rom_banks[window] as usize * ROM_WINDOW_SIZE + (address as usize & 0x1FFF))
+            & (FLASH_SIZE - 1)
+    }
+
+    // Macronix-style command sequences: two unlock writes to 0x5555 and
+    // 0x2AAA, then program, chip erase or sector erase. Programming can
+    // only clear bits. Returns true when the flash contents changed.
+    fn write_flash(&mut self, offset: usize, byte: u8) -> bool {
+        if byte == 0xF0 {
+            self.flash_state = FlashState::Idle;
+            return false;
+        }
+
+        let flash = &mut self.ram[self.sram_size..];
+        let mut changed = false;
+        let command = offset & 0x7FFF;
+        self.flash_state = match (self.flash_state, command, byte) {
+            (FlashState::Idle, 0x5555, 0xAA) => FlashState::Unlocked1,
+            (FlashState::Unlocked1, 0x2AAA, 0x55) => FlashState::Unlocked2,
+            (FlashState::Unlocked2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Unlocked2, 0x5555, 0x80) => FlashState::EraseArmed,
+            (FlashState::EraseArmed, 0x5555, 0xAA) => FlashState::EraseUnlocked1,
+            (FlashState::EraseUnlocked1, 0x2AAA, 0x55) => FlashState::EraseUnlocked2,
+            (FlashState::EraseUnlocked2, 0x5555, 0x10) => {
+                changed = erase(flash);
+                FlashState::Idle
+            }
+            (FlashState::EraseUnlocked2, _, 0x30) => {
+                let sector = offset & !(FLASH_SECTOR_SIZE - 1);
+                changed = erase(&mut flash[sector..sector + FLASH_SECTOR_SIZE]);
+                FlashState::Idle
+            }
+            (FlashState::Program, _, _) => {
+                let programmed = flash[offset] & byte;
+                changed = store(&mut flash[offset], programmed);
+                FlashState::Idle
+            }
+            _ => FlashState::Idle,
+        };
+        changed
+    }
+}
+
+// Sets erased flash back to 0xFF, returns true if any byte wasn't already
+fn erase(flash: &mut [u8]) -> bool {
+    let changed = flash.iter().any(|&b| b != 0xFF);
+    flash.fill(0xFF);
+    changed
+}
+
+impl Mapper for MBC6 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if address < 0x4000 {
+            return self.rom[address as usize & (self.rom.len() - 1)];
+        }
+
+        let window = Self::window(address);
+        if self.flash_selected[window] {
+            if !self.flash_enabled {
+                return 0xFF;
+            }
+            return self.ram[self.sram_size + self.flash_offset(window, address)];
+        }
+        let bank = self.rom_banks[window] as usize;
+        self.rom[banked_offset(&self.rom, ROM_WINDOW_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x03FF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x0400..=0x07FF => self.ram_banks[0] = byte & 0x07,
+            0x0800..=0x0BFF => self.ram_banks[1] = byte & 0x07,
+            0x0C00..=0x0FFF => self.flash_enabled = byte & 0x01 != 0,
+            0x1000 => self.flash_write_enabled = byte & 0x01 != 0,
+            0x2000..=0x27FF => self.rom_banks[0] = byte & 0x7F,
+            0x2800..=0x2FFF => self.flash_selected[0] = byte == 0x08,
+            0x3000..=0x37FF => self.rom_banks[1] = byte & 0x7F,
+            0x3800..=0x3FFF => self.flash_selected[1] = byte == 0x08,
+            0x4000..=0x7FFF => {
+                let window = Self::window(address);
+                if self.flash_selected[window] && self.flash_enabled && self.flash_write_enabled {
+                    let offset = self.flash_offset(window, address);
+                    return self.write_flash(offset, byte);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.sram_size == 0 {
+            return 0xFF;
+        }
+        let window = ((address >> 12) & 0x01) as usize;
+        let sram = &self.ram[..self.sram_size];
+        sram[banked_offset(
+            sram,
+            RAM_WINDOW_SIZE,
+            self.ram_banks[window] as usize,
+            address,
+        )]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if !self.ram_enabled || self.sram_size == 0 {
+            return false;
+        }
+        let window = ((address >> 12) & 0x01) as usize;
+        let sram = &mut self.ram[..self.sram_size];
+        let offset = banked_offset(
+            sram,
+            RAM_WINDOW_SIZE,
+            self.ram_banks[window] as usize,
+            address,
+        );
+        store(&mut sram[offset], byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flash_program_and_erase() {
+        let mut mbc = MBC6::new(vec![0; 0x100000], 0x8000);
+        mbc.write_rom(0x0C00, 0x01);
+        mbc.write_rom(0x1000, 0x01);
+        mbc.write_rom(0x2800, 0x08);
+        mbc.write_rom(0x3800, 0x08);
+
+        let command = |mbc: &mut MBC6, bank: u8, offset: u16, byte: u8| {
+            // 0x5555 is bank 2 offset 0x1555, 0x2AAA bank 1 offset 0x0AAA
+            mbc.write_rom(0x2000, bank);
+            mbc.write_rom(0x4000 | offset, byte);
+        };
+        let erase_chip = |mbc: &mut MBC6| {
+            for (bank, offset, byte) in [
+                (2, 0x1555, 0xAA),
+                (1, 0x0AAA, 0x55),
+                (2, 0x1555, 0x80),
+                (2, 0x1555, 0xAA),
+                (1, 0x0AAA, 0x55),
+                (2, 0x1555, 0x10),
+            ] {
+                command(mbc, bank, offset, byte);
+            }
+        };
+
+        erase_chip(&mut mbc);
+        mbc.write_rom(0x3000, 0x05);
+        assert_eq!(mbc.read_rom(0x6010), 0xFF);
+
+        command(&mut mbc, 2, 0x1555, 0xAA);
+        command(&mut mbc, 1, 0x0AAA, 0x55);
+        command(&mut mbc, 2, 0x1555, 0xA0);
+        assert!(mbc.write_rom(0x6010, 0x3C));
+        assert_eq!(mbc.read_rom(0x6010), 0x3C);
+
+        // without the unlock sequence writes are ignored
+        assert!(!mbc.write_rom(0x6010, 0x00));
+        assert_eq!(mbc.read_rom(0x6010), 0x3C);
+    }
+}
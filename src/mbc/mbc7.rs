@@ -0,0 +1,345 @@
+use super::{Mapper, ROM_BANK_SIZE, banked_offset};
+
+const EEPROM_SIZE: usize = 0x100;
+
+// Accelerometer reading for a level cart, and how far one g moves it
+const ACCEL_CENTER: f32 = 0x81D0 as f32;
+const ACCEL_PER_G: f32 = 0x70 as f32;
+
+const EEPROM_DO: u8 = 0x01;
+const EEPROM_DI: u8 = 0x02;
+const EEPROM_CLK: u8 = 0x40;
+const EEPROM_CS: u8 = 0x80;
+
+#[derive(Clone, Copy, PartialEq)]
+enum EepromState {
+    // waiting for the start bit
+    Idle,
+    // shifting in the 2-bit opcode and 8-bit address
+    Command {
+        bits: u8,
+        value: u16,
+    },
+    // shifting in 16 bits to store at `address`, or everywhere
+    Data {
+        address: Option<u8>,
+        bits: u8,
+        value: u16,
+    },
+    // shifting a word out on DO
+    Read {
+        bits: u8,
+        value: u16,
+    },
+}
+
+// MBC7 (Kirby Tilt 'n' Tumble, Command Master) has a two-axis
+// accelerometer and a 93LC56 serial EEPROM instead of RAM, both reached
+// through registers in 0xA000..=0xAFFF. The tilt comes from the host.
+pub struct MBC7 {
+    rom: Vec<u8>,
+    // the EEPROM's 128 16-bit words, little-endian
+    eeprom: Vec<u8>,
+    ram_enabled: [bool; 2],
+    rom_bank: u8,
+    tilt: (f32, f32),
+    latched: (u16, u16),
+    latch_ready: bool,
+    // last value written to the EEPROM pins
+    pins: u8,
+    data_out: bool,
+    write_enabled: bool,
+    state: EepromState,
+}
+
+impl MBC7 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        MBC7 {
+            rom,
+            eeprom: vec![0xFF; EEPROM_SIZE],
+            ram_enabled: [false; 2],
+            rom_bank: 1,
+            tilt: (0.0, 0.0),
+            latched: (0x8000, 0x8000),
+            latch_ready: false,
+            pins: 0,
+            data_out: true,
+            write_enabled: false,
+            state: EepromState::Idle,
+        }
+    }
+
+    fn word(&self, address: u8) -> u16 {
+        let i = (address & 0x7F) as usize * 2;
+        u16::from_le_bytes([self.eeprom[i], self.eeprom[i + 1]])
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        let i = (address & 0x7F) as usize * 2;
+        self.eeprom[i..i + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_pins(&mut self, byte: u8) {
+        let old = self.pins;
+        self.pins = byte & (EEPROM_CS | EEPROM_CLK | EEPROM_DI);
+
+        if byte & EEPROM_CS == 0 {
+            self.state = EepromState::Idle;
+            return;
+        }
+        // everything happens on the rising edge of CLK
+        if old & EEPROM_CLK != 0 || byte & EEPROM_CLK == 0 {
+            return;
+        }
+
+        let bit = (byte & EEPROM_DI != 0) as u16;
+        self.state = match self.state {
+            EepromState::Idle if bit == 1 => EepromState::Command { bits: 0, value: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { bits, value } => {
+                let value = value << 1 | bit;
+                if bits + 1 < 10 {
+                    EepromState::Command {
+                        bits: bits + 1,
+                        value,
+                    }
+                } else {
+                    self.command(value)
+                }
+            }
+            EepromState::Data {
+                address,
+                bits,
+                value,
+            } => {
+                let value = value << 1 | bit;
+                if bits + 1 < 16 {
+                    EepromState::Data {
+                        address,
+                        bits: bits + 1,
+                        value,
+                    }
+                } else {
+                    if self.write_enabled {
+                        match address {
+                            Some(address) => self.set_word(address, value),
+                            None => {
+                                for address in 0..0x80 {
+                                    self.set_word(address, value);
+                                }
+                            }
+                        }
+                    }
+                    // writes complete instantly, so DO reports ready
+                    self.data_out = true;
+                    EepromState::Idle
+                }
+            }
+            EepromState::Read { bits, value } => {
+                self.data_out = value & 0x8000 != 0;
+                if bits > 1 {
+                    EepromState::Read {
+                        bits: bits - 1,
+                        value: value << 1,
+                    }
+                } else {
+                    EepromState::Idle
+                }
+            }
+        };
+    }
+
+    fn command(&mut self, command: u16) -> EepromState {
+        let address = command as u8;
+        match command >> 8 {
+            0b10 => {
+                // a dummy 0 comes out before the data
+                self.data_out = false;
+                EepromState::Read {
+                    bits: 16,
+                    value: self.word(address),
+                }
+            }
+            0b01 => EepromState::Data {
+                address: Some(address),
+                bits: 0,
+                value: 0,
+            },
+            0b11 => {
+                if self.write_enabled {
+                    self.set_word(address, 0xFFFF);
+                }
+                self.data_out = true;
+                EepromState::Idle
+            }
+            _ => match address >> 6 {
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Idle
+                }
+                0b11 => {
+                    self.write_enabled = true;
+                    EepromState::Idle
+                }
+                0b10 => {
+                    if self.write_enabled {
+                        self.eeprom.fill(0xFF);
+                    }
+                    self.data_out = true;
+                    EepromState::Idle
+                }
+                _ => EepromState::Data {
+                    address: None,
+                    bits: 0,
+                    value: 0,
+                },
+            },
+        }
+    }
+
+    fn accelerometer(&self) -> (u16, u16) {
+        let axis = |g: f32| (ACCEL_CENTER + g * ACCEL_PER_G) as u16;
+        (axis(self.tilt.0), axis(self.tilt.1))
+    }
+}
+
+impl Mapper for MBC7 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.eeprom
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.eeprom
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled[0] = byte == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.ram_enabled[1] = byte == 0x40,
+            _ => {}
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !(self.ram_enabled[0] && self.ram_enabled[1]) || address >= 0xB000 {
+            return 0xFF;
+        }
+
+        match (address >> 4) & 0x0F {
+            0x2 => self.latched.0 as u8,
+            0x3 => (self.latched.0 >> 8) as u8,
+            0x4 => self.latched.1 as u8,
+            0x5 => (self.latched.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.pins | if self.data_out { EEPROM_DO } else { 0 },
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if !(self.ram_enabled[0] && self.ram_enabled[1]) || address >= 0xB000 {
+            return false;
+        }
+
+        match (address >> 4) & 0x0F {
+            // erase, then latch a fresh reading
+            0x0 if byte == 0x55 => {
+                self.latched = (0x8000, 0x8000);
+                self.latch_ready = true;
+            }
+            0x1 if byte == 0xAA && self.latch_ready => {
+                self.latched = self.accelerometer();
+                self.latch_ready = false;
+            }
+            // only EEPROM writes and erases touch the saved data
+            0x8 => {
+                let before = self.eeprom.clone();
+                self.write_pins(byte);
+                return self.eeprom != before;
+            }
+            _ => {}
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_bits(mbc: &mut MBC7, bits: &[u8]) -> Vec<u8> {
+        bits.iter()
+            .map(|&bit| {
+                let di = if bit != 0 { EEPROM_DI } else { 0 };
+                mbc.write_ram(0xA080, EEPROM_CS | di);
+                mbc.write_ram(0xA080, EEPROM_CS | EEPROM_CLK | di);
+                mbc.read_ram(0xA080) & EEPROM_DO
+            })
+            .collect()
+    }
+
+    fn bits(value: u32, count: u32) -> Vec<u8> {
+        (0..count).rev().map(|i| (value >> i) as u8 & 1).collect()
+    }
+
+    // start bit, 2-bit opcode and 8-bit address
+    fn command_bits(opcode: u32, address: u32) -> Vec<u8> {
+        bits(0x400 | opcode << 8 | address, 11)
+    }
+
+    #[test]
+    fn eeprom_write_then_read() {
+        let mut mbc = MBC7::new(vec![0; 4 * ROM_BANK_SIZE]);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x40);
+
+        // EWEN, then WRITE 0xBEEF to word 5
+        clock_bits(&mut mbc, &command_bits(0b00, 0xC0));
+        mbc.write_ram(0xA080, 0x00);
+        let mut write = command_bits(0b01, 5);
+        write.extend(bits(0xBEEF, 16));
+        clock_bits(&mut mbc, &write);
+        mbc.write_ram(0xA080, 0x00);
+        assert_eq!(mbc.word(5), 0xBEEF);
+
+        // READ word 5: a dummy 0, then the data MSB first
+        clock_bits(&mut mbc, &command_bits(0b10, 5));
+        assert_eq!(mbc.read_ram(0xA080) & EEPROM_DO, 0);
+        let out = clock_bits(&mut mbc, &[0; 16]);
+        assert_eq!(out, bits(0xBEEF, 16));
+    }
+
+    #[test]
+    fn accelerometer_latch() {
+        let mut mbc = MBC7::new(vec![0; 4 * ROM_BANK_SIZE]);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x40);
+        mbc.set_tilt(1.0, 0.0);
+
+        mbc.write_ram(0xA010, 0xAA);
+        assert_eq!(mbc.read_ram(0xA030), 0x80);
+        mbc.write_ram(0xA000, 0x55);
+        mbc.write_ram(0xA010, 0xAA);
+        assert_eq!(mbc.read_ram(0xA020), 0x40);
+        assert_eq!(mbc.read_ram(0xA030), 0x82);
+        assert_eq!(mbc.read_ram(0xA040), 0xD0);
+    }
+}
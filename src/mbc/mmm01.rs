@@ -0,0 +1,195 @@
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, banked_offset, store};
+
+// MMM01 multicarts boot into a menu stored in the last 32 KiB of ROM.
+// The menu programs the outer bank bits and masks for the chosen game,
+// then sets the map bit, which locks those settings and hands the game
+// an MBC1-like view of its own slice of the ROM.
+pub struct MMM01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mapped: bool,
+    ram_enabled: bool,
+    // RB0-4, the only ROM bits the game itself changes
+    rom_bank_low: u8,
+    // RB5-6 and RB7-8, set by the menu
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    // RB1-4 bits the game may no longer change once mapped
+    rom_bank_mask: u8,
+    // RA13-14 and RA15-16
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    ram_bank_mask: u8,
+    mode: u8,
+    mode_locked: bool,
+}
+
+impl MMM01 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        MMM01 {
+            rom,
+            ram: vec![0; ram_size],
+            mapped: false,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            mode: 0,
+            mode_locked: false,
+        }
+    }
+
+    fn outer_bank(&self) -> usize {
+        ((self.rom_bank_high as usize) << 7) | ((self.rom_bank_mid as usize) << 5)
+    }
+
+    fn fixed_rom_bits(&self) -> u8 {
+        (self.rom_bank_mask & 0x0F) << 1
+    }
+
+    fn rom_bank(&self, address: u16) -> usize {
+        // until the menu maps a game, every bank line is pulled high and
+        // the last 32 KiB is all that's visible
+        if !self.mapped {
+            let banks = self.rom.len() / ROM_BANK_SIZE;
+            return match address {
+                0x0000..=0x3FFF => banks.saturating_sub(2),
+                _ => banks - 1,
+            };
+        }
+
+        match address {
+            0x0000..=0x3FFF => {
+                self.outer_bank() | (self.rom_bank_low & self.fixed_rom_bits()) as usize
+            }
+            _ => {
+                // like MBC1 the game can't select its own bank 0 here
+                let mut low = self.rom_bank_low;
+                if low & !self.fixed_rom_bits() == 0 {
+                    low |= 1;
+                }
+                self.outer_bank() | low as usize
+            }
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let low = if self.mode == 0 {
+            self.ram_bank_low & self.ram_bank_mask
+        } else {
+            self.ram_bank_low
+        };
+        let bank = (self.ram_bank_high << 2) | low;
+        banked_offset(&self.ram, RAM_BANK_SIZE, bank as usize, address)
+    }
+}
+
+impl Mapper for MMM01 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, self.rom_bank(address), address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = byte & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.ram_bank_mask = (byte >> 4) & 0x03;
+                    self.mapped = byte & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let fixed = if self.mapped {
+                    self.fixed_rom_bits()
+                } else {
+                    0
+                };
+                self.rom_bank_low = (self.rom_bank_low & fixed) | (byte & 0x1F & !fixed);
+                if !self.mapped {
+                    self.rom_bank_mid = (byte >> 5) & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                let fixed = if self.mapped { self.ram_bank_mask } else { 0 };
+                self.ram_bank_low = (self.ram_bank_low & fixed) | (byte & 0x03 & !fixed);
+                if !self.mapped {
+                    self.ram_bank_high = (byte >> 2) & 0x03;
+                    self.rom_bank_high = (byte >> 4) & 0x03;
+                    self.mode_locked = byte & 0x40 != 0;
+                }
+            }
+            _ => {
+                if !self.mode_locked {
+                    self.mode = byte & 0x01;
+                }
+                if !self.mapped {
+                    self.rom_bank_mask = (byte >> 2) & 0x0F;
+                }
+            }
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return false;
+        }
+        let offset = self.ram_offset(address);
+        store(&mut self.ram[offset], byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_maps_and_locks_a_game() {
+        let mut rom = vec![0; 64 * ROM_BANK_SIZE];
+        for bank in 0..64 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let mut mbc = MMM01::new(rom, 0);
+
+        assert_eq!(mbc.read_rom(0x0000), 62);
+        assert_eq!(mbc.read_rom(0x4000), 63);
+
+        // pick the 4-bank game at bank 0x24: RB5 set, RB2-4 locked
+        mbc.write_rom(0x2000, 0x24);
+        mbc.write_rom(0x6000, 0x38);
+        mbc.write_rom(0x0000, 0x40);
+        assert_eq!(mbc.read_rom(0x0000), 0x24);
+        assert_eq!(mbc.read_rom(0x4000), 0x25);
+
+        // the game can only move RB0-1 now
+        mbc.write_rom(0x2000, 0x1F);
+        assert_eq!(mbc.read_rom(0x4000), 0x27);
+        mbc.write_rom(0x2000, 0x02);
+        assert_eq!(mbc.read_rom(0x4000), 0x26);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0x25);
+    }
+}
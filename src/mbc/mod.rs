@@ -0,0 +1,72 @@
+mod huc1;
+mod huc3;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod pocket_camera;
+mod rom_only;
+mod rtc;
+mod tama5;
+
+pub use huc1::HuC1;
+pub use huc3::HuC3;
+pub use mbc1::MBC1;
+pub use mbc2::MBC2;
+pub use mbc3::MBC3;
+pub use mbc5::MBC5;
+pub use mbc6::MBC6;
+pub use mbc7::MBC7;
+pub use mmm01::MMM01;
+pub use pocket_camera::PocketCamera;
+pub use rom_only::RomOnly;
+pub use tama5::TAMA5;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+// Called with the new motor state whenever a rumble cart toggles it
+pub type RumbleCallback = Box<dyn FnMut(bool)>;
+
+// Banking hardware on the cartridge. The bus hands it every access to
+// 0x0000..=0x7FFF and 0xA000..=0xBFFF; writes to the ROM area program
+// the mapper's registers.
+pub trait Mapper {
+    fn rom(&self) -> &[u8];
+    fn ram(&self) -> &[u8];
+    fn ram_mut(&mut self) -> &mut [u8];
+    fn read_rom(&self, address: u16) -> u8;
+    // Like `write_ram`, returns true when saved state changed, which ROM
+    // writes only do on carts with flash
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool;
+    fn read_ram(&self, address: u16) -> u8;
+    // Returns true when the write changed RAM or other saved state, so
+    // games writing to disabled RAM don't cause needless saves
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool;
+
+    // Extra state saved after the RAM, such as a real-time clock
+    fn clock_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_clock_data(&mut self, _data: &[u8]) {}
+
+    fn set_rumble_callback(&mut self, _callback: RumbleCallback) {}
+
+    // Tilt in g along the X and Y axes, for carts with an accelerometer
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+}
+
+// Writes `byte` to a saved location, returns true if it was different
+fn store(cell: &mut u8, byte: u8) -> bool {
+    std::mem::replace(cell, byte) != byte
+}
+
+// Offset of `address` within the given bank, wrapped to the image size.
+// Every mapped image is a power of two long, so masking is enough.
+fn banked_offset(data: &[u8], bank_size: usize, bank: usize, address: u16) -> usize {
+    (bank * bank_size + (address as usize & (bank_size - 1))) & (data.len() - 1)
+}
@@ -0,0 +1,142 @@
+use super::{Mapper, RAM_BANK_SIZE, ROM_BANK_SIZE, banked_offset, store};
+
+const CAMERA_SELECT: u8 = 0x10;
+const CAPTURE_BUSY: u8 = 0x01;
+const REGISTER_COUNT: usize = 0x36;
+
+// Captured images land in RAM bank 0 as 16x14 tiles
+const IMAGE_START: usize = 0x0100;
+const IMAGE_SIZE: usize = 16 * 14 * 16;
+
+// The Game Boy Camera's MAC-GBD mapper. Setting bit 4 of the RAM bank
+// register swaps RAM for the sensor registers. There is no sensor to
+// read, so a capture finishes at once and leaves a blank picture.
+pub struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTER_COUNT],
+}
+
+impl PocketCamera {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        PocketCamera {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; REGISTER_COUNT],
+        }
+    }
+
+    fn camera_selected(&self) -> bool {
+        self.ram_bank & CAMERA_SELECT != 0
+    }
+
+    fn capture(&mut self) {
+        if self.ram.len() >= IMAGE_START + IMAGE_SIZE {
+            self.ram[IMAGE_START..IMAGE_START + IMAGE_SIZE].fill(0);
+        }
+        self.registers[0] &= !CAPTURE_BUSY;
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        banked_offset(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for PocketCamera {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, address: u16, byte: u8) -> bool {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x1F,
+            _ => {}
+        }
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.camera_selected() {
+            // only the control register can be read back
+            return match address & 0x7F {
+                0x00 => self.registers[0],
+                _ => 0x00,
+            };
+        }
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if self.camera_selected() {
+            let register = (address & 0x7F) as usize;
+            if register < REGISTER_COUNT {
+                self.registers[register] = byte;
+                // a capture fills the image area of RAM
+                if register == 0 && byte & CAPTURE_BUSY != 0 {
+                    self.capture();
+                    return true;
+                }
+            }
+            return false;
+        }
+        if !self.ram_enabled || self.ram.is_empty() {
+            return false;
+        }
+        let offset = self.ram_offset(address);
+        store(&mut self.ram[offset], byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_registers_and_capture() {
+        let mut mbc = PocketCamera::new(vec![0; 4 * ROM_BANK_SIZE], 16 * RAM_BANK_SIZE);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x77);
+        mbc.write_ram(0xA000 + IMAGE_START as u16, 0x55);
+
+        // bit 4 of the bank swaps RAM for the registers
+        mbc.write_rom(0x4000, 0x10);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        assert_eq!(mbc.read_ram(0xA001), 0x00);
+        assert!(!mbc.write_ram(0xA001, 0x12));
+
+        // the capture finishes at once and clears the image area
+        assert!(mbc.write_ram(0xA000, 0x03));
+        assert_eq!(mbc.read_ram(0xA000), 0x02);
+
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x77);
+        assert_eq!(mbc.read_ram(0xA000 + IMAGE_START as u16), 0x00);
+    }
+}
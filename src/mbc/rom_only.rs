@@ -0,0 +1,53 @@
+use super::{Mapper, store};
+
+// 32 KiB carts wired straight to the bus, optionally with up to 8 KiB
+// of RAM at 0xA000
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        RomOnly {
+            rom,
+            ram: vec![0; ram_size],
+        }
+    }
+}
+
+impl Mapper for RomOnly {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _byte: u8) -> bool {
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram
+            .get((address - 0xA000) as usize)
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        match self.ram.get_mut((address - 0xA000) as usize) {
+            Some(cell) => store(cell, byte),
+            None => false,
+        }
+    }
+}
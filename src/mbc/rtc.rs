@@ -0,0 +1,278 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 86400;
+pub const SAVE_SIZE: usize = 48;
+
+const DH_DAY_HIGH: u8 = 0x01;
+const DH_HALT: u8 = 0x40;
+const DH_CARRY: u8 = 0x80;
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+struct Registers {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    carry: bool,
+}
+
+impl Registers {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days as u8,
+            0x0C => {
+                let mut dh = (self.days >> 8) as u8 & DH_DAY_HIGH;
+                if self.halt {
+                    dh |= DH_HALT;
+                }
+                if self.carry {
+                    dh |= DH_CARRY;
+                }
+                dh
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, register: u8, byte: u8) {
+        match register {
+            0x08 => self.seconds = byte & 0x3F,
+            0x09 => self.minutes = byte & 0x3F,
+            0x0A => self.hours = byte & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | byte as u16,
+            0x0C => {
+                self.days = (self.days & 0xFF) | ((byte & DH_DAY_HIGH) as u16) << 8;
+                self.halt = byte & DH_HALT != 0;
+                self.carry = byte & DH_CARRY != 0;
+            }
+            _ => {}
+        }
+    }
+
+    // One tick of the hardware counters. Out-of-range values written by
+    // the game count up to the register width and wrap without carrying.
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.add_days(1);
+    }
+
+    fn add_days(&mut self, days: u64) {
+        let days = self.days as u64 + days;
+        if days > 0x1FF {
+            self.carry = true;
+        }
+        self.days = (days & 0x1FF) as u16;
+    }
+
+    fn is_valid(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        // step through invalid states the way the hardware would, then
+        // jump the rest of the way arithmetically
+        while seconds > 0 && !self.is_valid() {
+            self.tick_second();
+            seconds -= 1;
+        }
+
+        let time_of_day = self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600;
+        let total = time_of_day + seconds;
+        self.add_days(total / SECONDS_PER_DAY);
+        let time_of_day = total % SECONDS_PER_DAY;
+        self.hours = (time_of_day / 3600) as u8;
+        self.minutes = (time_of_day / 60 % 60) as u8;
+        self.seconds = (time_of_day % 60) as u8;
+    }
+}
+
+// The MBC3 real-time clock. Rather than counting emulated cycles it
+// follows the host clock, so time keeps passing while the emulator is
+// closed. Games read a latched copy of the counters.
+pub struct Rtc {
+    current: Registers,
+    latched: Registers,
+    // host time the current registers correspond to
+    timestamp: u64,
+    latch_armed: bool,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Rtc {
+            current: Registers::default(),
+            latched: Registers::default(),
+            timestamp: unix_time(),
+            latch_armed: false,
+        }
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    pub fn write(&mut self, register: u8, byte: u8) {
+        self.update(unix_time());
+        self.current.write(register, byte);
+        // writes show up in the latched copy right away
+        self.latched.write(register, byte);
+    }
+
+    // Writing 0x00 then 0x01 copies the running counters into the
+    // readable registers
+    pub fn write_latch(&mut self, byte: u8) {
+        if self.latch_armed && byte == 0x01 {
+            self.update(unix_time());
+            self.latched = self.current;
+        }
+        self.latch_armed = byte == 0x00;
+    }
+
+    fn update(&mut self, now: u64) {
+        if !self.current.halt {
+            self.current.advance(now.saturating_sub(self.timestamp));
+        }
+        self.timestamp = now;
+    }
+
+    // The 48-byte trailer shared by BGB, VBA-M, SameBoy and others:
+    // current then latched registers as 32-bit little-endian words,
+    // followed by a 64-bit UNIX timestamp.
+    pub fn save(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(SAVE_SIZE);
+        for registers in [&self.current, &self.latched] {
+            for register in 0x08..=0x0C {
+                data.extend_from_slice(&(registers.read(register) as u32).to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&self.timestamp.to_le_bytes());
+        data
+    }
+
+    // Accepts the 48-byte trailer and the older 44-byte variant with a
+    // 32-bit timestamp, then catches up to the current host time
+    pub fn load(&mut self, data: &[u8]) {
+        self.load_at(data, unix_time());
+    }
+
+    fn load_at(&mut self, data: &[u8], now: u64) {
+        let word = |i: usize| u32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+        let timestamp = match data.len() {
+            48.. => u64::from_le_bytes(data[40..48].try_into().unwrap()),
+            44..48 => word(10) as u64,
+            _ => return,
+        };
+
+        for (i, register) in (0x08..=0x0C).enumerate() {
+            self.current.write(register, word(i) as u8);
+            self.latched.write(register, word(i + 5) as u8);
+        }
+        self.timestamp = timestamp;
+        self.update(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_carry_and_wrap() {
+        let mut registers = Registers {
+            seconds: 59,
+            minutes: 59,
+            hours: 23,
+            days: 0x1FF,
+            ..Registers::default()
+        };
+        registers.advance(1);
+        assert_eq!(
+            registers,
+            Registers {
+                carry: true,
+                ..Registers::default()
+            }
+        );
+
+        // invalid seconds count up to 63 and wrap without carrying
+        let mut registers = Registers {
+            seconds: 62,
+            ..Registers::default()
+        };
+        registers.advance(3);
+        assert_eq!((registers.seconds, registers.minutes), (1, 0));
+
+        let mut registers = Registers::default();
+        registers.advance(3 * SECONDS_PER_DAY + 3661);
+        assert_eq!(
+            (
+                registers.days,
+                registers.hours,
+                registers.minutes,
+                registers.seconds
+            ),
+            (3, 1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn latch_needs_zero_then_one() {
+        let mut rtc = Rtc::new();
+        rtc.write(0x0C, DH_HALT);
+        rtc.write(0x09, 42);
+        rtc.current.minutes = 7;
+
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(0x09), 42);
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(0x09), 7);
+    }
+
+    #[test]
+    fn save_round_trip_catches_up() {
+        let mut rtc = Rtc::new();
+        rtc.timestamp = 1000;
+        rtc.current.minutes = 5;
+        rtc.current.halt = false;
+        let data = rtc.save();
+        assert_eq!(data.len(), SAVE_SIZE);
+
+        let mut restored = Rtc::new();
+        restored.load_at(&data, 1000 + 90);
+        assert_eq!(restored.current.minutes, 6);
+        assert_eq!(restored.current.seconds, 30);
+        assert_eq!(restored.timestamp, 1090);
+
+        // a halted clock ignores the time that passed
+        rtc.current.halt = true;
+        let mut restored = Rtc::new();
+        restored.load_at(&rtc.save(), 5000);
+        assert_eq!(restored.current.minutes, 5);
+    }
+}
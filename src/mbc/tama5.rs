@@ -0,0 +1,132 @@
+use super::{Mapper, ROM_BANK_SIZE, banked_offset, store};
+
+const MEMORY_SIZE: usize = 0x20;
+
+// Bandai's TAMA5 (Game de Hakken!! Tamagotchi 3). Everything goes through
+// two ports: writing 0xA001 selects a 4-bit register and 0xA000 reads or
+// writes it. The TAMA6 clock behind it isn't emulated, only its 32 bytes
+// of battery-backed memory.
+pub struct TAMA5 {
+    rom: Vec<u8>,
+    memory: Vec<u8>,
+    selected: u8,
+    registers: [u8; 0x10],
+    output: u8,
+}
+
+impl TAMA5 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        TAMA5 {
+            rom,
+            memory: vec![0; MEMORY_SIZE],
+            selected: 0,
+            registers: [0; 0x10],
+            output: 0,
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        (((self.registers[1] & 0x01) << 4) | self.registers[0]) as usize
+    }
+
+    // Writing the low address nibble runs the command held in register 6.
+    // Returns true when it changed the saved memory.
+    fn execute(&mut self) -> bool {
+        let address = (((self.registers[6] & 0x01) << 4) | self.registers[7]) as usize;
+        match self.registers[6] >> 1 {
+            0x0 => {
+                let value = (self.registers[5] << 4) | self.registers[4];
+                return store(&mut self.memory[address], value);
+            }
+            0x1 => self.output = self.memory[address],
+            // clock commands read back as zero
+            _ => self.output = 0,
+        }
+        false
+    }
+}
+
+impl Mapper for TAMA5 {
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank(),
+        };
+        self.rom[banked_offset(&self.rom, ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_rom(&mut self, _address: u16, _byte: u8) -> bool {
+        false
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if address & 0x01 != 0 {
+            return 0xFF;
+        }
+
+        let nibble = match self.selected {
+            // always ready for the next command
+            0x0A => 0x01,
+            0x0C => self.output & 0x0F,
+            0x0D => self.output >> 4,
+            _ => 0x0F,
+        };
+        0xF0 | nibble
+    }
+
+    fn write_ram(&mut self, address: u16, byte: u8) -> bool {
+        if address & 0x01 != 0 {
+            self.selected = byte & 0x0F;
+            return false;
+        }
+
+        self.registers[self.selected as usize] = byte & 0x0F;
+        self.selected == 0x07 && self.execute()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_register(mbc: &mut TAMA5, register: u8, value: u8) {
+        mbc.write_ram(0xA001, register);
+        mbc.write_ram(0xA000, value);
+    }
+
+    #[test]
+    fn memory_round_trip_and_banking() {
+        let mut rom = vec![0; 32 * ROM_BANK_SIZE];
+        rom[0x13 * ROM_BANK_SIZE] = 0x13;
+        let mut mbc = TAMA5::new(rom);
+
+        write_register(&mut mbc, 0, 0x3);
+        write_register(&mut mbc, 1, 0x1);
+        assert_eq!(mbc.read_rom(0x4000), 0x13);
+
+        write_register(&mut mbc, 4, 0xD);
+        write_register(&mut mbc, 5, 0xB);
+        write_register(&mut mbc, 6, 0x1);
+        write_register(&mut mbc, 7, 0x2);
+        assert_eq!(mbc.memory[0x12], 0xBD);
+
+        write_register(&mut mbc, 6, 0x3);
+        write_register(&mut mbc, 7, 0x2);
+        mbc.write_ram(0xA001, 0x0D);
+        assert_eq!(mbc.read_ram(0xA000), 0xFB);
+        mbc.write_ram(0xA001, 0x0C);
+        assert_eq!(mbc.read_ram(0xA000), 0xFD);
+    }
+}
@@ -0,0 +1,33 @@
+// Hardware revisions. They mostly differ in the state the boot ROM
+// leaves behind, which matters when the boot ROM is skipped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    DMG0,
+    DMG,
+    MGB,
+    SGB,
+    CGB,
+}
+
+impl Model {
+    pub fn from_name(name: &str) -> Option<Model> {
+        match name.to_ascii_lowercase().as_str() {
+            "dmg0" => Some(Model::DMG0),
+            "dmg" => Some(Model::DMG),
+            "mgb" => Some(Model::MGB),
+            "sgb" => Some(Model::SGB),
+            "cgb" => Some(Model::CGB),
+            _ => None,
+        }
+    }
+
+    pub fn boot_rom_name(self) -> &'static str {
+        match self {
+            Model::DMG0 => "dmg0_boot.bin",
+            Model::DMG => "dmg_boot.bin",
+            Model::MGB => "mgb_boot.bin",
+            Model::SGB => "sgb_boot.bin",
+            Model::CGB => "cgb_boot.bin",
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::ppu::SHADES;
+
+// Color schemes for the four DMG shades, lightest first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Gray,
+    // the original DMG's green LCD
+    Green,
+    Pocket,
+    // the Game Boy Light's backlit screen
+    Light,
+    Custom([u32; 4]),
+}
+
+impl Palette {
+    // A scheme name, or four RGB colors like "E0F8D0 88C070 346856 081820"
+    pub fn parse(value: &str) -> Option<Palette> {
+        match value.to_ascii_lowercase().as_str() {
+            "gray" | "grey" => return Some(Palette::Gray),
+            "green" | "dmg" => return Some(Palette::Green),
+            "pocket" => return Some(Palette::Pocket),
+            "light" => return Some(Palette::Light),
+            _ => {}
+        }
+
+        let colors: Vec<u32> = value
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|color| !color.is_empty())
+            .map(|color| {
+                let hex = color.trim_start_matches('#').trim_start_matches("0x");
+                // from_str_radix alone would also take a leading sign
+                if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                u32::from_str_radix(hex, 16).ok()
+            })
+            .collect::<Option<_>>()?;
+        Some(Palette::Custom(colors.try_into().ok()?))
+    }
+
+    pub fn shades(self) -> [u32; 4] {
+        match self {
+            Palette::Gray => SHADES,
+            Palette::Green => [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F],
+            Palette::Pocket => [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F],
+            Palette::Light => [0x00B581, 0x009A71, 0x00694A, 0x004F3B],
+            Palette::Custom(shades) => shades,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_custom_colors() {
+        assert_eq!(Palette::parse("Green"), Some(Palette::Green));
+        assert_eq!(
+            Palette::parse("#E0F8D0 0x88C070, 346856 081820"),
+            Some(Palette::Custom([0xE0F8D0, 0x88C070, 0x346856, 0x081820]))
+        );
+        assert_eq!(Palette::parse("E0F8D0 88C070 346856"), None);
+        assert_eq!(Palette::parse("E0F8D0 88C070 346856 08182"), None);
+        assert_eq!(Palette::parse("+FFFFF 88C070 346856 081820"), None);
+        assert_eq!(Palette::parse("E0F8D0 88C070 346856 08182G"), None);
+        assert_eq!(Palette::parse("sepia"), None);
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+
+use super::{OAM_SCAN_END, OBJ_BEHIND_BG, OBJ_PALETTE_1, OBJ_X_FLIP, PPU, SCREEN_WIDTH, Sprite};
+
+// Tile number, low byte and high byte take 2 dots each
+const FETCH_DOTS: u8 = 6;
+
+#[derive(Clone, Copy, Default)]
+struct ObjPixel {
+    color: u8,
+    flags: u8,
+}
+
+// Fetcher and FIFO state for the line being drawn
+pub(super) struct PixelFifo {
+    bg: VecDeque<u8>,
+    obj: VecDeque<ObjPixel>,
+    // sprites on this line in fetch order, removed once fetched
+    sprites: Vec<Sprite>,
+    sprite_fetch: Option<(Sprite, u8)>,
+    // dots spent on the current tile, and the tile column being fetched
+    fetch_dots: u8,
+    fetch_x: u8,
+    tile: u8,
+    low: u8,
+    high: u8,
+    // the first fetch of a line is thrown away
+    dummy_fetch: bool,
+    // pixels to drop before the first one is shown, from SCX or WX < 7
+    discard: u8,
+    lx: u8,
+    in_window: bool,
+    pub(super) drawing: bool,
+    mode3_dots: u32,
+}
+
+impl PixelFifo {
+    pub(super) fn new() -> Self {
+        PixelFifo {
+            bg: VecDeque::with_capacity(16),
+            obj: VecDeque::with_capacity(8),
+            sprites: Vec::new(),
+            sprite_fetch: None,
+            fetch_dots: 0,
+            fetch_x: 0,
+            tile: 0,
+            low: 0,
+            high: 0,
+            dummy_fetch: true,
+            discard: 0,
+            lx: 0,
+            in_window: false,
+            drawing: false,
+            mode3_dots: 0,
+        }
+    }
+}
+
+impl PPU {
+    // Runs dots `from..to` of the current visible line. Drawing starts
+    // as soon as the OAM scan is over, so mode 3 begins right after it.
+    pub(super) fn run_fifo(&mut self, from: u32, to: u32) {
+        for dot in from..to {
+            if self.fifo.drawing && self.draw_dot() {
+                self.finish_drawing();
+            }
+            if dot + 1 == OAM_SCAN_END {
+                self.start_drawing();
+            }
+        }
+    }
+
+    fn start_drawing(&mut self) {
+        if self.ly == self.wy {
+            self.wy_triggered = true;
+        }
+
+        let mut sprites = self.sprites_on_line(self.ly as usize);
+        sprites.sort_by_key(|&(index, sprite)| (sprite.x, index));
+
+        self.fifo = PixelFifo {
+            sprites: sprites.into_iter().map(|(_, sprite)| sprite).collect(),
+            discard: self.scx % 8,
+            drawing: true,
+            ..PixelFifo::new()
+        };
+    }
+
+    fn finish_drawing(&mut self) {
+        self.fifo.drawing = false;
+        if self.fifo.in_window {
+            self.window_line += 1;
+        }
+    }
+
+    // One dot of mode 3, returns true once the last pixel is out
+    fn draw_dot(&mut self) -> bool {
+        self.fifo.mode3_dots += 1;
+
+        // a sprite fetch stalls both the BG fetcher and the shifter
+        if let Some((sprite, dots)) = self.fifo.sprite_fetch {
+            if dots + 1 < FETCH_DOTS {
+                self.fifo.sprite_fetch = Some((sprite, dots + 1));
+            } else {
+                self.fifo.sprite_fetch = None;
+                self.merge_sprite(sprite);
+            }
+            return false;
+        }
+
+        // a sprite at the current X waits until the BG fetch in progress
+        // reaches its high byte, up to 5 dots
+        if let Some(index) = self.pending_sprite() {
+            if self.fifo.fetch_dots >= FETCH_DOTS - 1 && !self.fifo.bg.is_empty() {
+                let sprite = self.fifo.sprites.remove(index);
+                self.fifo.sprite_fetch = Some((sprite, 1));
+            } else {
+                self.step_fetcher();
+            }
+            return false;
+        }
+
+        if self.shift_pixel() {
+            return true;
+        }
+        self.step_fetcher();
+        false
+    }
+
+    fn pending_sprite(&self) -> Option<usize> {
+        if self.lcdc & 0x02 == 0 {
+            return None;
+        }
+        // sprites hanging off the left edge are fetched at X 0
+        let lx = self.fifo.lx as i16;
+        self.fifo.sprites.iter().position(|s| s.x.max(0) == lx)
+    }
+
+    fn window_starts(&self) -> bool {
+        !self.fifo.in_window
+            && self.lcdc & 0x20 != 0
+            && self.wy_triggered
+            && self.wx <= 166
+            && self.fifo.lx as u16 + 7 >= self.wx as u16
+    }
+
+    // Pushes one pixel to the LCD, returns true after the last one
+    fn shift_pixel(&mut self) -> bool {
+        if self.fifo.bg.is_empty() {
+            return false;
+        }
+
+        // reaching WX restarts the fetcher on the window map
+        if self.window_starts() {
+            self.fifo.in_window = true;
+            self.fifo.bg.clear();
+            self.fifo.fetch_dots = 0;
+            self.fifo.fetch_x = 0;
+            self.fifo.discard = if self.fifo.lx == 0 {
+                7u8.saturating_sub(self.wx)
+            } else {
+                0
+            };
+            return false;
+        }
+
+        let bg = self.fifo.bg.pop_front().unwrap_or(0);
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return false;
+        }
+        let obj = self.fifo.obj.pop_front().unwrap_or_default();
+
+        // registers are read as each pixel leaves, so writes during
+        // mode 3 show up from the next pixel on
+        let bg = if self.lcdc & 0x01 != 0 { bg } else { 0 };
+        let mut shade = self.shade(self.bgp, bg);
+        if self.lcdc & 0x02 != 0 && obj.color != 0 && (obj.flags & OBJ_BEHIND_BG == 0 || bg == 0) {
+            let palette = if obj.flags & OBJ_PALETTE_1 != 0 {
+                self.obp1
+            } else {
+                self.obp0
+            };
+            shade = self.shade(palette, obj.color);
+        }
+
+        let y = self.ly as usize;
+        self.buffer[y * SCREEN_WIDTH + self.fifo.lx as usize] = shade;
+        self.fifo.lx += 1;
+        self.fifo.lx as usize == SCREEN_WIDTH
+    }
+
+    fn step_fetcher(&mut self) {
+        self.fifo.fetch_dots = (self.fifo.fetch_dots + 1).min(FETCH_DOTS + 1);
+
+        match self.fifo.fetch_dots {
+            2 => self.fifo.tile = self.fetch_tile_number(),
+            4 => self.fifo.low = self.vram[self.fetch_row_address()],
+            6 => self.fifo.high = self.vram[self.fetch_row_address() + 1],
+            _ => {}
+        }
+
+        // the finished tile waits until the BG FIFO has run dry
+        if self.fifo.fetch_dots >= FETCH_DOTS && self.fifo.bg.is_empty() {
+            self.fifo.fetch_dots = 0;
+            if self.fifo.dummy_fetch {
+                self.fifo.dummy_fetch = false;
+                return;
+            }
+            for bit in (0..8).rev() {
+                let low = (self.fifo.low >> bit) & 1;
+                let high = (self.fifo.high >> bit) & 1;
+                self.fifo.bg.push_back((high << 1) | low);
+            }
+            self.fifo.fetch_x = self.fifo.fetch_x.wrapping_add(1);
+        }
+    }
+
+    // Map base, X and Y within the map of the tile being fetched
+    fn fetch_position(&self) -> (usize, u8, u8) {
+        let column = self.fifo.fetch_x.wrapping_mul(8);
+        if self.fifo.in_window {
+            let map_base = if (self.lcdc & 0x40) != 0 {
+                0x1C00
+            } else {
+                0x1800
+            };
+            (map_base, column, self.window_line)
+        } else {
+            let map_base = if (self.lcdc & 0x08) != 0 {
+                0x1C00
+            } else {
+                0x1800
+            };
+            (
+                map_base,
+                column.wrapping_add(self.scx & 0xF8),
+                self.ly.wrapping_add(self.scy),
+            )
+        }
+    }
+
+    fn fetch_tile_number(&self) -> u8 {
+        let (map_base, map_x, map_y) = self.fetch_position();
+        self.vram[map_base + (map_y / 8) as usize * 32 + (map_x / 8) as usize]
+    }
+
+    fn fetch_row_address(&self) -> usize {
+        let (_, _, map_y) = self.fetch_position();
+        self.tile_address(self.fifo.tile) + (map_y & 7) as usize * 2
+    }
+
+    // Mixes a fetched sprite into the OBJ FIFO. Pixels already there came
+    // from sprites further left or earlier in OAM, so they keep priority
+    // unless transparent.
+    fn merge_sprite(&mut self, sprite: Sprite) {
+        let (low, high) = self.sprite_row(sprite, self.ly as usize);
+
+        for col in 0..8 {
+            let offset = sprite.x + col - self.fifo.lx as i16;
+            if offset < 0 {
+                continue;
+            }
+            let bit = if sprite.flags & OBJ_X_FLIP != 0 {
+                col
+            } else {
+                7 - col
+            };
+            let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+
+            let offset = offset as usize;
+            while self.fifo.obj.len() <= offset {
+                self.fifo.obj.push_back(ObjPixel::default());
+            }
+            if self.fifo.obj[offset].color == 0 {
+                self.fifo.obj[offset] = ObjPixel {
+                    color,
+                    flags: sprite.flags,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{CYCLES_PER_SCANLINE, SHADES};
+    use super::*;
+
+    fn mode3_length(ppu: &mut PPU) -> u32 {
+        ppu.run_fifo(0, CYCLES_PER_SCANLINE);
+        assert!(!ppu.fifo.drawing);
+        ppu.fifo.mode3_dots
+    }
+
+    #[test]
+    fn mode3_length_depends_on_scroll_window_and_sprites() {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x91;
+        assert_eq!(mode3_length(&mut ppu), 172);
+
+        ppu.scx = 3;
+        assert_eq!(mode3_length(&mut ppu), 175);
+
+        ppu.scx = 0;
+        ppu.lcdc = 0xB1;
+        ppu.wx = 7 + 80;
+        assert_eq!(mode3_length(&mut ppu), 178);
+
+        // a sprite costs 6 dots plus the wait for the BG fetcher
+        ppu.lcdc = 0x93;
+        ppu.oam[..4].copy_from_slice(&[16, 8 + 40, 0, 0]);
+        assert_eq!(mode3_length(&mut ppu), 172 + 11);
+        ppu.oam[..4].copy_from_slice(&[16, 8 + 45, 0, 0]);
+        assert_eq!(mode3_length(&mut ppu), 172 + 6);
+    }
+
+    #[test]
+    fn matches_the_line_renderer() {
+        let mut fifo = PPU::new();
+        fifo.scx = 13;
+        fifo.scy = 5;
+        fifo.wx = 7 + 100;
+        fifo.bgp = 0xD2;
+        fifo.obp0 = 0xE4;
+        fifo.obp1 = 0x1B;
+        for (i, byte) in fifo.vram[..0x1800].iter_mut().enumerate() {
+            *byte = (i * 7 + i / 16) as u8;
+        }
+        for (i, byte) in fifo.vram[0x1800..].iter_mut().enumerate() {
+            *byte = (i * 3) as u8;
+        }
+        for i in 0..10 {
+            let sprite = [16 + i as u8, 3 + i as u8 * 17, i as u8 * 5, (i as u8) << 4];
+            fifo.oam[i * 4..i * 4 + 4].copy_from_slice(&sprite);
+        }
+
+        // the same scene with BG and window enabled and disabled
+        for lcdc in [0xF3, 0xF2] {
+            fifo.lcdc = lcdc;
+            fifo.window_line = 0;
+            fifo.wy_triggered = false;
+
+            let mut line = PPU::new();
+            line.vram = fifo.vram;
+            line.oam = fifo.oam;
+            (line.lcdc, line.scx, line.scy, line.wx) = (fifo.lcdc, fifo.scx, fifo.scy, fifo.wx);
+            (line.bgp, line.obp0, line.obp1) = (fifo.bgp, fifo.obp0, fifo.obp1);
+
+            for y in 0..24 {
+                fifo.ly = y as u8;
+                fifo.run_fifo(0, CYCLES_PER_SCANLINE);
+                line.render_line(y);
+                let row = y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH;
+                assert_eq!(
+                    fifo.buffer[row.clone()],
+                    line.buffer[row],
+                    "LCDC {:#04x} line {}",
+                    lcdc,
+                    y
+                );
+            }
+            assert_eq!(fifo.window_line, line.window_line);
+        }
+    }
+
+    #[test]
+    fn mid_line_writes_take_effect_at_the_next_pixel() {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x91;
+        ppu.bgp = 0xE4;
+        ppu.vram[..0x10].fill(0xFF);
+
+        // the first pixel leaves 12 dots into mode 3
+        let first_pixel = OAM_SCAN_END + 12;
+        ppu.run_fifo(0, first_pixel + 50);
+        ppu.lcdc = 0x90;
+        ppu.run_fifo(first_pixel + 50, CYCLES_PER_SCANLINE);
+
+        assert_eq!(ppu.buffer[49], SHADES[3]);
+        assert_eq!(ppu.buffer[50], SHADES[0]);
+    }
+}
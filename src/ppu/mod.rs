@@ -0,0 +1,653 @@
+mod fifo;
+
+use crate::dma::OAM_SIZE;
+use fifo::PixelFifo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+pub const CYCLES_PER_SCANLINE: u32 = 456;
+const CYCLES_PER_FRAME: u32 = CYCLES_PER_SCANLINE * 154;
+
+// OAM scan takes 80 dots and drawing at least 172 more; each line is
+// rendered in one go once drawing would have finished
+const OAM_SCAN_END: u32 = 80;
+const MODE3_END: u32 = OAM_SCAN_END + 172;
+
+// With the `pixel-fifo` feature mode 3 runs dot by dot through the BG and
+// OBJ pixel FIFOs instead of drawing whole lines
+const PIXEL_FIFO: bool = cfg!(feature = "pixel-fifo");
+
+const SPRITES_PER_LINE: usize = 10;
+// Default host colors for the four shades, lightest first
+pub const SHADES: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+
+// STAT interrupt sources
+const STAT_MODE0: u8 = 0x08;
+const STAT_MODE1: u8 = 0x10;
+const STAT_MODE2: u8 = 0x20;
+const STAT_LYC: u8 = 0x40;
+
+// OAM attribute flags
+const OBJ_BEHIND_BG: u8 = 0x80;
+const OBJ_Y_FLIP: u8 = 0x40;
+const OBJ_X_FLIP: u8 = 0x20;
+const OBJ_PALETTE_1: u8 = 0x10;
+
+#[derive(Clone, Copy)]
+struct Sprite {
+    y: i16,
+    x: i16,
+    tile: u8,
+    flags: u8,
+}
+
+pub struct PPU {
+    pub vram: [u8; 0x2000],
+    pub oam: [u8; OAM_SIZE],
+    pub buffer: [u32; SCREEN_HEIGHT * SCREEN_WIDTH],
+    pub lcdc: u8,
+    pub stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+    pub frame_ready: bool,
+    // host color of each shade the palette registers can pick
+    pub shades: [u32; 4],
+    // internal line counter of the window and whether LY has hit WY
+    window_line: u8,
+    wy_triggered: bool,
+    line_cycles: u32,
+    fifo: PixelFifo,
+    // the STAT interrupt fires on rising edges of the OR of its sources
+    stat_line: bool,
+    stat_interrupt: bool,
+    // dots since the LCD was switched off, to keep frames coming
+    off_cycles: u32,
+}
+
+impl PPU {
+    pub fn new() -> Self {
+        PPU {
+            vram: [0; 0x2000],
+            oam: [0; OAM_SIZE],
+            buffer: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            frame_ready: false,
+            shades: SHADES,
+            window_line: 0,
+            wy_triggered: false,
+            line_cycles: 0,
+            fifo: PixelFifo::new(),
+            stat_line: false,
+            stat_interrupt: false,
+            off_cycles: 0,
+        }
+    }
+
+    // Advances the PPU by `cycles` T-cycles, returns true on entering VBlank
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        let mut vblank = false;
+        for _ in 0..cycles {
+            vblank |= self.step();
+        }
+        vblank
+    }
+
+    // Advances one dot, mode changes and STAT edges happen on dot boundaries
+    fn step(&mut self) -> bool {
+        // with the LCD off LY stays at 0, but the host still wants frames
+        if (self.lcdc & 0x80) == 0 {
+            self.off_cycles += 1;
+            if self.off_cycles == CYCLES_PER_FRAME {
+                self.off_cycles = 0;
+                self.frame_ready = true;
+            }
+            return false;
+        }
+
+        let visible = (self.ly as usize) < SCREEN_HEIGHT;
+        if visible && PIXEL_FIFO {
+            self.run_fifo(self.line_cycles, self.line_cycles + 1);
+        }
+        self.line_cycles += 1;
+        if visible && !PIXEL_FIFO && self.line_cycles == MODE3_END {
+            self.render_line(self.ly as usize);
+        }
+
+        let mut vblank = false;
+        if self.line_cycles == CYCLES_PER_SCANLINE {
+            self.line_cycles = 0;
+            self.ly += 1;
+
+            if self.ly == 144 {
+                self.frame_ready = true;
+                vblank = true;
+            }
+
+            if self.ly >= 154 {
+                self.ly = 0;
+                self.window_line = 0;
+                self.wy_triggered = false;
+            }
+        }
+
+        self.update_stat_line();
+        vblank
+    }
+
+    // 0: HBlank, 1: VBlank, 2: OAM scan, 3: drawing
+    fn mode(&self) -> u8 {
+        let drawing = if PIXEL_FIFO {
+            self.fifo.drawing
+        } else {
+            self.line_cycles < MODE3_END
+        };
+
+        if (self.lcdc & 0x80) == 0 {
+            0
+        } else if (self.ly as usize) >= SCREEN_HEIGHT {
+            1
+        } else if self.line_cycles < OAM_SCAN_END {
+            2
+        } else if drawing {
+            3
+        } else {
+            0
+        }
+    }
+
+    // Sources that are already active hold the line high, so a new one
+    // only interrupts once they have all dropped ("STAT blocking")
+    fn update_stat_line(&mut self) {
+        let line = match self.mode() {
+            0 => self.stat & STAT_MODE0 != 0,
+            1 => self.stat & STAT_MODE1 != 0,
+            2 => self.stat & STAT_MODE2 != 0,
+            _ => false,
+        } || (self.stat & STAT_LYC != 0 && self.ly_matches());
+
+        if line && !self.stat_line {
+            self.stat_interrupt = true;
+        }
+        self.stat_line = line;
+    }
+
+    // LY as the CPU sees it, line 153 already reads 0 after 4 dots
+    fn visible_ly(&self) -> u8 {
+        if self.ly == 153 && self.line_cycles >= 4 {
+            0
+        } else {
+            self.ly
+        }
+    }
+
+    // The LY=LYC compare sees nothing for the 4 dots where LY drops to 0
+    fn ly_matches(&self) -> bool {
+        if self.ly == 153 && (4..8).contains(&self.line_cycles) {
+            return false;
+        }
+        self.visible_ly() == self.lyc
+    }
+
+    // The boot ROM hands over during line 153, with LY already reading 0
+    pub fn skip_boot(&mut self, line_cycles: u32) {
+        self.ly = 153;
+        self.line_cycles = line_cycles;
+        self.update_stat_line();
+    }
+
+    // Whether a STAT interrupt was raised since the last call
+    pub fn take_stat_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.stat_interrupt)
+    }
+
+    // The CPU can't reach VRAM while it's being drawn from, nor OAM during
+    // the OAM scan and drawing
+    pub fn vram_accessible(&self) -> bool {
+        self.mode() != 3
+    }
+
+    pub fn oam_accessible(&self) -> bool {
+        self.mode() < 2
+    }
+
+    fn set_lcdc(&mut self, byte: u8) {
+        let was_on = (self.lcdc & 0x80) != 0;
+        self.lcdc = byte;
+
+        if was_on && (byte & 0x80) == 0 {
+            self.ly = 0;
+            self.line_cycles = 0;
+            self.fifo = PixelFifo::new();
+            self.buffer.fill(self.shades[0]);
+        } else if !was_on && (byte & 0x80) != 0 {
+            self.off_cycles = 0;
+            self.window_line = 0;
+            self.wy_triggered = false;
+        }
+    }
+
+    // LCD registers 0xFF40..=0xFF4B, except DMA (0xFF46) which the bus owns
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            0xFF40 => self.lcdc,
+            0xFF41 => {
+                let coincidence = if self.ly_matches() { 0x04 } else { 0x00 };
+                0x80 | (self.stat & 0x78) | coincidence | self.mode()
+            }
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.visible_ly(),
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF40 => self.set_lcdc(byte),
+            // only the interrupt enable bits are writable
+            0xFF41 => self.stat = byte & 0x78,
+            0xFF42 => self.scy = byte,
+            0xFF43 => self.scx = byte,
+            // LY is read-only
+            0xFF44 => {}
+            0xFF45 => self.lyc = byte,
+            0xFF47 => self.bgp = byte,
+            0xFF48 => self.obp0 = byte,
+            0xFF49 => self.obp1 = byte,
+            0xFF4A => self.wy = byte,
+            0xFF4B => self.wx = byte,
+            _ => {}
+        }
+        self.update_stat_line();
+    }
+
+    // Draws line `y` with the register values in effect right now, so
+    // changes made between lines show up like on hardware
+    fn render_line(&mut self, y: usize) {
+        let mut bg_colors = [0u8; SCREEN_WIDTH];
+        self.render_background(y, &mut bg_colors);
+        self.render_window(y, &mut bg_colors);
+
+        // with LCDC bit 0 clear the window still counts its lines, but BG
+        // and window both show color 0 and never hide sprites
+        if self.lcdc & 0x01 == 0 {
+            bg_colors.fill(0);
+            let blank = self.shade(self.bgp, 0);
+            self.buffer[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH].fill(blank);
+        }
+
+        if self.lcdc & 0x02 != 0 {
+            self.render_sprites(y, &bg_colors);
+        }
+    }
+
+    // Color index of a BG or window pixel from the tile map at `map_base`
+    fn map_pixel(&self, map_base: usize, map_x: u8, map_y: u8) -> u8 {
+        let tile_idx = (map_y / 8) as usize * 32 + (map_x / 8) as usize;
+        let tile_addr = self.tile_address(self.vram[map_base + tile_idx]);
+
+        let line = (map_y & 7) as usize;
+        let b1 = self.vram[tile_addr + line * 2];
+        let b2 = self.vram[tile_addr + line * 2 + 1];
+
+        let bit_idx = 7 - (map_x & 7);
+        let low_bit = (b1 >> bit_idx) & 1;
+        let high_bit = (b2 >> bit_idx) & 1;
+        (high_bit << 1) | low_bit
+    }
+
+    // Host color of a color index mapped through BGP, OBP0 or OBP1
+    fn shade(&self, palette: u8, color: u8) -> u32 {
+        self.shades[((palette >> (color * 2)) & 0x03) as usize]
+    }
+
+    // VRAM offset of a BG or window tile, honouring LCDC bit 4 addressing
+    fn tile_address(&self, tile_id: u8) -> usize {
+        if (self.lcdc & 0x10) != 0 {
+            tile_id as usize * 16
+        } else {
+            let signed_id = tile_id as i8 as i16;
+            (0x1000i16 + signed_id * 16) as usize
+        }
+    }
+
+    // Draws a line of BG and records each pixel's color index, which
+    // decides whether sprites behind the BG show through
+    fn render_background(&mut self, y: usize, bg_colors: &mut [u8; SCREEN_WIDTH]) {
+        let map_base = if (self.lcdc & 0x08) != 0 {
+            0x1C00
+        } else {
+            0x1800
+        };
+        let map_y = (y as u8).wrapping_add(self.scy);
+
+        for (x, bg_color) in bg_colors.iter_mut().enumerate() {
+            let map_x = (x as u8).wrapping_add(self.scx);
+            let color_val = self.map_pixel(map_base, map_x, map_y);
+            *bg_color = color_val;
+            self.buffer[y * SCREEN_WIDTH + x] = self.shade(self.bgp, color_val);
+        }
+    }
+
+    // The window covers the BG from (WX - 7, WY) to the bottom right. It
+    // only starts once LY has matched WY during the frame, and its own line
+    // counter only moves on lines where it was actually drawn.
+    fn render_window(&mut self, y: usize, bg_colors: &mut [u8; SCREEN_WIDTH]) {
+        if y == self.wy as usize {
+            self.wy_triggered = true;
+        }
+        if self.lcdc & 0x20 == 0 || !self.wy_triggered || self.wx > 166 {
+            return;
+        }
+
+        let map_base = if (self.lcdc & 0x40) != 0 {
+            0x1C00
+        } else {
+            0x1800
+        };
+        let left = self.wx as i16 - 7;
+
+        for (x, bg_color) in bg_colors.iter_mut().enumerate() {
+            let window_x = x as i16 - left;
+            if window_x < 0 {
+                continue;
+            }
+            let color_val = self.map_pixel(map_base, window_x as u8, self.window_line);
+            *bg_color = color_val;
+            self.buffer[y * SCREEN_WIDTH + x] = self.shade(self.bgp, color_val);
+        }
+
+        self.window_line += 1;
+    }
+
+    // The first 10 sprites in OAM order that cover line `y`
+    fn sprites_on_line(&self, y: usize) -> Vec<(usize, Sprite)> {
+        let height = if self.lcdc & 0x04 != 0 { 16 } else { 8 };
+
+        self.oam
+            .chunks_exact(4)
+            .map(|entry| Sprite {
+                y: entry[0] as i16 - 16,
+                x: entry[1] as i16 - 8,
+                tile: entry[2],
+                flags: entry[3],
+            })
+            .enumerate()
+            .filter(|(_, sprite)| (sprite.y..sprite.y + height).contains(&(y as i16)))
+            .take(SPRITES_PER_LINE)
+            .collect()
+    }
+
+    // Low and high bitplanes of the row of `sprite` that lands on line `y`
+    fn sprite_row(&self, sprite: Sprite, y: usize) -> (u8, u8) {
+        let tall = self.lcdc & 0x04 != 0;
+        let height = if tall { 16 } else { 8 };
+
+        let mut row = (y as i16 - sprite.y) & (height - 1);
+        if sprite.flags & OBJ_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+        // 8x16 sprites ignore bit 0 and use the next tile for the bottom half
+        let tile = if tall {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        let address = tile as usize * 16 + row as usize * 2;
+        (self.vram[address], self.vram[address + 1])
+    }
+
+    fn render_sprites(&mut self, y: usize, bg_colors: &[u8; SCREEN_WIDTH]) {
+        // on DMG the sprite further left wins, then the one earlier in OAM
+        let mut sprites = self.sprites_on_line(y);
+        sprites.sort_by_key(|&(index, sprite)| (sprite.x, index));
+
+        for (x, &bg_color) in bg_colors.iter().enumerate() {
+            for &(_, sprite) in &sprites {
+                let col = x as i16 - sprite.x;
+                if !(0..8).contains(&col) {
+                    continue;
+                }
+
+                let (low, high) = self.sprite_row(sprite, y);
+                let bit = if sprite.flags & OBJ_X_FLIP != 0 {
+                    col
+                } else {
+                    7 - col
+                };
+                let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+                if color == 0 {
+                    continue;
+                }
+
+                // the first opaque sprite decides, even when it ends up
+                // hidden behind the BG
+                if sprite.flags & OBJ_BEHIND_BG == 0 || bg_color == 0 {
+                    let palette = if sprite.flags & OBJ_PALETTE_1 != 0 {
+                        self.obp1
+                    } else {
+                        self.obp0
+                    };
+                    self.buffer[y * SCREEN_WIDTH + x] = self.shade(palette, color);
+                }
+                break;
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn debug_draw_tiles(&mut self) {
+        let mut xdraw = 0;
+        let mut ydraw = 0;
+
+        for i in (0..0x1800).step_by(16) {
+            self.draw_tile(i, xdraw, ydraw);
+
+            xdraw += 8;
+            if xdraw >= SCREEN_WIDTH {
+                xdraw = 0;
+                ydraw += 8;
+            }
+
+            if ydraw >= SCREEN_HEIGHT {
+                break;
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    fn draw_tile(&mut self, start: usize, x: usize, y: usize) {
+        for row in 0..8 {
+            let byte1 = self.vram[start + (row * 2)];
+            let byte2 = self.vram[start + (row * 2) + 1];
+
+            for col in 0..8 {
+                let bit_idx = 7 - col;
+
+                let low_bit = (byte1 >> bit_idx) & 1;
+                let high_bit = (byte2 >> bit_idx) & 1;
+
+                let color_val = (high_bit << 1) | low_bit;
+
+                let color = match color_val {
+                    0 => 0xFFFFFF,
+                    1 => 0xAAAAAA,
+                    2 => 0x555555,
+                    3 => 0x000000,
+                    _ => 0x000000,
+                };
+
+                let fx = x + col;
+                let fy = y + row;
+
+                if fx < SCREEN_WIDTH && fy < SCREEN_HEIGHT {
+                    let pix_idx = fy * SCREEN_WIDTH + fx;
+                    self.buffer[pix_idx] = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_sprite(ppu: &mut PPU, index: usize, y: u8, x: u8, tile: u8, flags: u8) {
+        ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[y, x, tile, flags]);
+    }
+
+    #[test]
+    fn sprite_priority_and_line_limit() {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x93;
+        ppu.obp0 = 0xE4;
+        // tile 1 is solid color 1, tile 2 solid color 3
+        ppu.vram[0x10..0x20].copy_from_slice(&[0xFF, 0x00].repeat(8));
+        ppu.vram[0x20..0x30].fill(0xFF);
+
+        // the later sprite further left wins where they overlap
+        set_sprite(&mut ppu, 0, 16, 12, 1, 0);
+        set_sprite(&mut ppu, 1, 16, 8, 2, 0);
+        // an 11th sprite on the line is dropped
+        for i in 2..11 {
+            set_sprite(&mut ppu, i, 16, 40 + i as u8 * 8, 2, 0);
+        }
+        ppu.render_line(0);
+
+        assert_eq!(ppu.buffer[4], SHADES[3]);
+        assert_eq!(ppu.buffer[8], SHADES[1]);
+        assert_eq!(ppu.buffer[40 + 9 * 8 - 8], SHADES[3]);
+        assert_eq!(ppu.buffer[40 + 10 * 8 - 8], SHADES[0]);
+    }
+
+    #[test]
+    fn window_line_counter_skips_hidden_lines() {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0xF1;
+        ppu.wy = 2;
+        ppu.wx = 7 + 80;
+        // window map at 0x9C00: tile row 0 uses tile 1, row 1 tile 2
+        ppu.vram[0x1C00..0x1C20].fill(1);
+        ppu.vram[0x1C20..0x1C40].fill(2);
+        ppu.vram[0x10..0x20].copy_from_slice(&[0xFF, 0x00].repeat(8));
+        ppu.vram[0x20..0x30].fill(0xFF);
+
+        let mut colors = [0; SCREEN_WIDTH];
+        for y in 0..20 {
+            // hide the window for lines 5-9
+            ppu.lcdc = if (5..10).contains(&y) { 0xD1 } else { 0xF1 };
+            ppu.render_window(y, &mut colors);
+        }
+
+        // 2-4 and 10-19 were drawn: 3 + 10 lines
+        assert_eq!(ppu.window_line, 13);
+        assert_eq!(colors[79], 0);
+        assert_eq!(colors[80], 3);
+    }
+
+    #[test]
+    fn scroll_changes_apply_from_the_next_line() {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x91;
+        ppu.bgp = 0xE4;
+        // tile 1 is solid color 3 and sits in the second map column
+        ppu.vram[0x10..0x20].fill(0xFF);
+        ppu.vram[0x1801] = 1;
+
+        ppu.tick(CYCLES_PER_SCANLINE);
+        ppu.scx = 8;
+        ppu.tick(CYCLES_PER_SCANLINE);
+
+        assert_eq!(ppu.buffer[8], SHADES[3]);
+        assert_eq!(ppu.buffer[0], SHADES[0]);
+        assert_eq!(ppu.buffer[SCREEN_WIDTH], SHADES[3]);
+        assert_eq!(ppu.buffer[SCREEN_WIDTH + 8], SHADES[0]);
+    }
+
+    #[test]
+    fn stat_reports_the_mode_and_lyc_match() {
+        let mut ppu = PPU::new();
+        ppu.write_register(0xFF45, 1);
+        assert_eq!(ppu.read_register(0xFF41), 0x80);
+
+        ppu.write_register(0xFF40, 0x91);
+        assert_eq!(ppu.read_register(0xFF41) & 0x03, 2);
+        ppu.tick(OAM_SCAN_END);
+        assert_eq!(ppu.read_register(0xFF41) & 0x03, 3);
+        ppu.tick(MODE3_END - OAM_SCAN_END);
+        assert_eq!(ppu.read_register(0xFF41) & 0x03, 0);
+
+        ppu.tick(CYCLES_PER_SCANLINE - MODE3_END);
+        assert_eq!(ppu.read_register(0xFF41), 0x86);
+
+        ppu.tick(CYCLES_PER_SCANLINE * 143);
+        assert_eq!(ppu.ly, 144);
+        assert_eq!(ppu.read_register(0xFF41) & 0x03, 1);
+    }
+
+    #[test]
+    fn stat_interrupt_only_on_rising_edges() {
+        let mut ppu = PPU::new();
+        ppu.write_register(0xFF40, 0x91);
+        ppu.write_register(0xFF45, 1);
+        ppu.write_register(0xFF41, STAT_MODE0 | STAT_LYC);
+
+        ppu.tick(MODE3_END);
+        assert!(ppu.take_stat_interrupt());
+
+        // HBlank still holds the line high when LY matches on line 1
+        ppu.tick(CYCLES_PER_SCANLINE - MODE3_END);
+        assert_eq!(ppu.read_register(0xFF41) & 0x04, 0x04);
+        assert!(!ppu.take_stat_interrupt());
+        ppu.tick(CYCLES_PER_SCANLINE);
+        assert!(!ppu.take_stat_interrupt());
+
+        // line 2 drops it during the OAM scan, so HBlank fires again
+        ppu.tick(MODE3_END);
+        assert!(ppu.take_stat_interrupt());
+    }
+
+    #[test]
+    fn palettes_pick_host_shades() {
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x93;
+        ppu.shades = [10, 20, 30, 40];
+        // BG color 1 everywhere, a color 3 sprite on top
+        ppu.vram[..0x10].copy_from_slice(&[0xFF, 0x00].repeat(8));
+        ppu.vram[0x10..0x20].fill(0xFF);
+        set_sprite(&mut ppu, 0, 16, 8, 1, OBJ_PALETTE_1);
+
+        ppu.bgp = 0b00_00_11_00;
+        ppu.obp1 = 0b10_00_00_00;
+        ppu.render_line(0);
+        assert_eq!(ppu.buffer[0], 30);
+        assert_eq!(ppu.buffer[8], 40);
+        ppu.bgp = 0b00_00_01_00;
+        ppu.render_line(0);
+        assert_eq!(ppu.buffer[8], 20);
+    }
+}
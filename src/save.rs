@@ -0,0 +1,183 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cartridge::Cartridge;
+
+// How long the game has to stop writing before RAM is flushed, and the
+// longest a write may go unsaved when the game never stops
+const QUIET_PERIOD: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+// Battery-backed cartridge RAM on disk as `<rom>.sav`. The file is the
+// raw RAM followed by any clock trailer, like other emulators write it.
+pub struct SaveFile {
+    path: PathBuf,
+    // when the oldest and newest unsaved writes happened
+    first_write: Option<Instant>,
+    last_write: Instant,
+}
+
+impl SaveFile {
+    pub fn new(rom_path: &str) -> Self {
+        SaveFile {
+            path: Path::new(rom_path).with_extension("sav"),
+            first_write: None,
+            last_write: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns false when there is no save file yet
+    pub fn load(&self, cartridge: &mut Cartridge) -> io::Result<bool> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                cartridge.load_save_data(&data);
+                Ok(true)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Called regularly by the frontend, writes the file once the game has
+    // been quiet for a moment
+    pub fn update(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        self.update_at(cartridge, Instant::now())
+    }
+
+    fn update_at(&mut self, cartridge: &mut Cartridge, now: Instant) -> io::Result<()> {
+        if cartridge.take_ram_written() {
+            self.last_write = now;
+            self.first_write.get_or_insert(now);
+        }
+
+        match self.first_write {
+            Some(first_write)
+                if now - self.last_write >= QUIET_PERIOD || now - first_write >= MAX_DELAY =>
+            {
+                self.flush(cartridge)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Goes through a temporary file so a crash mid-write can't leave a
+    // truncated save behind
+    pub fn flush(&mut self, cartridge: &Cartridge) -> io::Result<()> {
+        let temp_path = self.path.with_extension("sav.tmp");
+        fs::write(&temp_path, cartridge.save_data())?;
+        fs::rename(&temp_path, &self.path)?;
+        self.first_write = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // An MBC1 cartridge with 8KB of battery-backed RAM, already enabled
+    fn battery_cartridge() -> Cartridge {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        let mut cartridge = Cartridge::new(rom).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge
+    }
+
+    fn save_file(name: &str) -> SaveFile {
+        let dir = env::temp_dir().join(format!("dmg01-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        SaveFile::new(dir.join("game.gb").to_str().unwrap())
+    }
+
+    #[test]
+    fn flushes_after_a_quiet_period_or_the_max_delay() {
+        let mut save = save_file("debounce");
+        let mut cartridge = battery_cartridge();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        cartridge.write_ram(0xA000, 0x01);
+        save.update_at(&mut cartridge, at(0)).unwrap();
+        save.update_at(&mut cartridge, at(999)).unwrap();
+        assert!(!save.path().exists());
+        save.update_at(&mut cartridge, at(1000)).unwrap();
+        assert_eq!(fs::read(save.path()).unwrap()[0], 0x01);
+
+        // a game writing every half second still gets saved
+        for step in 0..=20u64 {
+            cartridge.write_ram(0xA000, step as u8 + 2);
+            save.update_at(&mut cartridge, at(2000 + step * 500))
+                .unwrap();
+            let saved = fs::read(save.path()).unwrap()[0];
+            assert_eq!(saved == step as u8 + 2, step == 20, "step {}", step);
+        }
+
+        fs::remove_dir_all(save.path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn mbc6_flash_writes_start_a_save() {
+        let mut save = save_file("flash");
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x20;
+        rom[0x0149] = 0x03;
+        let mut cartridge = Cartridge::new(rom).unwrap();
+        let start = Instant::now();
+
+        // flash enabled and writable, shown in the 0x4000 window
+        cartridge.write_rom(0x0C00, 0x01);
+        cartridge.write_rom(0x1000, 0x01);
+        cartridge.write_rom(0x2800, 0x08);
+        let command = |cartridge: &mut Cartridge, bank: u8, offset: u16, byte: u8| {
+            cartridge.write_rom(0x2000, bank);
+            cartridge.write_rom(0x4000 | offset, byte);
+        };
+        let unlock = |cartridge: &mut Cartridge, byte: u8| {
+            command(cartridge, 2, 0x1555, 0xAA);
+            command(cartridge, 1, 0x0AAA, 0x55);
+            command(cartridge, 2, 0x1555, byte);
+        };
+
+        unlock(&mut cartridge, 0x80);
+        command(&mut cartridge, 2, 0x1555, 0xAA);
+        command(&mut cartridge, 1, 0x0AAA, 0x55);
+        command(&mut cartridge, 0, 0x0010, 0x30);
+        assert!(cartridge.take_ram_written());
+
+        unlock(&mut cartridge, 0xA0);
+        command(&mut cartridge, 0, 0x0010, 0x3C);
+        save.update_at(&mut cartridge, start).unwrap();
+        save.update_at(&mut cartridge, start + QUIET_PERIOD)
+            .unwrap();
+        assert_eq!(fs::read(save.path()).unwrap()[0x8010], 0x3C);
+
+        fs::remove_dir_all(save.path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn flush_replaces_the_file_through_a_temporary() {
+        let mut save = save_file("flush");
+        let mut cartridge = battery_cartridge();
+        fs::write(save.path(), b"old").unwrap();
+
+        cartridge.write_ram(0xA123, 0x5A);
+        save.flush(&cartridge).unwrap();
+        assert_eq!(fs::read(save.path()).unwrap(), cartridge.save_data());
+        assert!(!save.path().with_extension("sav.tmp").exists());
+
+        let mut loaded = battery_cartridge();
+        assert!(save.load(&mut loaded).unwrap());
+        assert_eq!(loaded.read_ram(0xA123), 0x5A);
+
+        fs::remove_dir_all(save.path().parent().unwrap()).unwrap();
+    }
+}
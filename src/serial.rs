@@ -0,0 +1,97 @@
+// Each bit takes 512 T-cycles with the internal 8192 Hz clock
+const CYCLES_PER_BIT: u32 = 512;
+
+// SB (0xFF01) and SC (0xFF02). No link partner is emulated, so every
+// transfer shifts in 1s as if the cable were unplugged.
+pub struct Serial {
+    pub data: u8,
+    pub control: u8,
+    bits_left: u8,
+    cycles: u32,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Serial {
+            data: 0,
+            control: 0,
+            bits_left: 0,
+            cycles: 0,
+        }
+    }
+
+    // Advances a running transfer, returns true when it completes and the
+    // serial interrupt should be raised
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        // only transfers driven by the internal clock ever progress
+        if self.bits_left == 0 {
+            return false;
+        }
+
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_BIT && self.bits_left > 0 {
+            self.cycles -= CYCLES_PER_BIT;
+            self.data = (self.data << 1) | 1;
+            self.bits_left -= 1;
+        }
+
+        if self.bits_left == 0 {
+            self.control &= 0x7F;
+            return true;
+        }
+
+        false
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF01 => self.data,
+            0xFF02 => self.control | 0x7E,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF01 => self.data = byte,
+            0xFF02 => {
+                self.control = byte & 0x81;
+                if byte & 0x81 == 0x81 {
+                    self.bits_left = 8;
+                    self.cycles = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_clock_transfer_completes_after_8_bits() {
+        let mut serial = Serial::new();
+        serial.write(0xFF01, 0x00);
+        serial.write(0xFF02, 0x81);
+        assert_eq!(serial.read(0xFF02), 0xFF);
+
+        assert!(!serial.tick(CYCLES_PER_BIT * 8 - 1));
+        assert_eq!(serial.read(0xFF01), 0x7F);
+        assert!(serial.tick(1));
+        assert_eq!(serial.read(0xFF01), 0xFF);
+        assert_eq!(serial.read(0xFF02), 0x7F);
+        assert!(!serial.tick(CYCLES_PER_BIT * 8));
+    }
+
+    #[test]
+    fn external_clock_transfer_never_completes() {
+        let mut serial = Serial::new();
+        serial.write(0xFF01, 0x42);
+        serial.write(0xFF02, 0x80);
+        assert!(!serial.tick(CYCLES_PER_BIT * 16));
+        assert_eq!(serial.read(0xFF01), 0x42);
+        assert_eq!(serial.read(0xFF02), 0xFE);
+    }
+}
@@ -0,0 +1,163 @@
+// DIV is the upper byte of a 16-bit counter that runs every T-cycle.
+// TIMA counts falling edges of the counter bit selected by TAC.
+pub struct Timer {
+    counter: u16,
+    pub tima: u8,
+    pub tma: u8,
+    pub tac: u8,
+    reload_delay: u8,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Timer {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            reload_delay: 0,
+        }
+    }
+
+    // Advances the timer by `cycles` T-cycles, returns true when TIMA
+    // overflowed and the timer interrupt should be raised
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        let mut interrupt = false;
+
+        for _ in 0..cycles {
+            // after an overflow TIMA reads 0 for one M-cycle before
+            // being reloaded from TMA
+            if self.reload_delay > 0 {
+                self.reload_delay -= 1;
+                if self.reload_delay == 0 {
+                    self.tima = self.tma;
+                    interrupt = true;
+                }
+            }
+
+            let old_counter = self.counter;
+            self.counter = self.counter.wrapping_add(1);
+            if self.timer_bit(old_counter, self.tac) && !self.timer_bit(self.counter, self.tac) {
+                self.increment_tima();
+            }
+        }
+
+        interrupt
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF04 => (self.counter >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            0xFF07 => self.tac | 0xF8,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF04 => self.reset_div(),
+            0xFF05 => {
+                // writing during the reload delay cancels the reload
+                self.tima = byte;
+                self.reload_delay = 0;
+            }
+            0xFF06 => self.tma = byte,
+            0xFF07 => {
+                // changing TAC can itself produce a falling edge
+                let old_tac = self.tac;
+                self.tac = byte & 0x07;
+                if self.timer_bit(self.counter, old_tac) && !self.timer_bit(self.counter, self.tac)
+                {
+                    self.increment_tima();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Sets the internal counter directly, used to recreate post-boot state
+    pub fn set_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
+    // Resetting the counter can produce a falling edge too
+    pub fn reset_div(&mut self) {
+        if self.timer_bit(self.counter, self.tac) {
+            self.increment_tima();
+        }
+        self.counter = 0;
+    }
+
+    fn timer_bit(&self, counter: u16, tac: u8) -> bool {
+        if tac & 0x04 == 0 {
+            return false;
+        }
+
+        let bit = match tac & 0x03 {
+            0x00 => 9, // 4096 Hz
+            0x01 => 3, // 262144 Hz
+            0x02 => 5, // 65536 Hz
+            _ => 7,    // 16384 Hz
+        };
+        counter & (1 << bit) != 0
+    }
+
+    fn increment_tima(&mut self) {
+        let (value, overflow) = self.tima.overflowing_add(1);
+        self.tima = value;
+        if overflow {
+            self.reload_delay = 4;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_write_resets_the_counter() {
+        let mut timer = Timer::new();
+        timer.tick(0x1234);
+        assert_eq!(timer.read(0xFF04), 0x12);
+
+        timer.write(0xFF04, 0x99);
+        assert_eq!(timer.read(0xFF04), 0x00);
+        timer.tick(0xFF);
+        assert_eq!(timer.read(0xFF04), 0x00);
+        timer.tick(1);
+        assert_eq!(timer.read(0xFF04), 0x01);
+
+        // the reset is a falling edge when the selected bit was set
+        timer.write(0xFF07, 0x05);
+        timer.tick(0x08);
+        timer.write(0xFF04, 0x00);
+        assert_eq!(timer.read(0xFF05), 1);
+        assert_eq!(timer.read(0xFF07), 0xFD);
+    }
+
+    #[test]
+    fn tima_overflow_reloads_after_a_delay() {
+        let mut timer = Timer::new();
+        timer.tma = 0xAB;
+        timer.tima = 0xFF;
+        timer.write(0xFF07, 0x05);
+
+        // 262144 Hz, one increment every 16 T-cycles
+        assert!(!timer.tick(16));
+        assert_eq!(timer.read(0xFF05), 0x00);
+        assert!(!timer.tick(3));
+        assert_eq!(timer.read(0xFF05), 0x00);
+        assert!(timer.tick(1));
+        assert_eq!(timer.read(0xFF05), 0xAB);
+
+        // writing TIMA during the delay cancels the reload
+        timer.tima = 0xFF;
+        timer.tick(16);
+        timer.write(0xFF05, 0x10);
+        assert!(!timer.tick(4));
+        assert_eq!(timer.read(0xFF05), 0x10);
+    }
+}